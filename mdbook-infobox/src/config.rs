@@ -0,0 +1,372 @@
+use mdbook::errors::Result as MdbookResult;
+use mdbook::preprocess::PreprocessorContext;
+use pulldown_cmark::Options;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// Options read from the `[preprocessor.infobox]` table in `book.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct InfoboxConfig {
+    /// Render infoboxes with print-specific classes that don't float.
+    pub print_friendly: bool,
+    /// Add a divider row between an image and the field that follows it.
+    pub divider_after_image: bool,
+    /// Keep the title row visible while scrolling through long infoboxes.
+    pub sticky_header: bool,
+    /// Field names every infobox in the book must have.
+    pub required_fields: Vec<String>,
+    /// What to use as an image's title when it doesn't have one.
+    pub image_title_fallback: ImageTitleFallback,
+    /// Leave exactly one blank line between a rendered infobox and the
+    /// surrounding chapter contents.
+    pub trim_surrounding_blanks: bool,
+    /// Render prose that isn't part of any field as a full-width description row.
+    pub render_loose_content: bool,
+    /// Markup used for infobox images.
+    pub image_semantics: ImageSemantics,
+    /// Html inserted verbatim before every infobox.
+    pub prefix_html: Option<String>,
+    /// Html inserted verbatim after every infobox.
+    pub suffix_html: Option<String>,
+    /// Fields rendered inside an `<address>`, keeping their line breaks.
+    pub address_fields: Vec<String>,
+    /// Markdown extensions enabled when parsing infobox contents.
+    pub extensions: Vec<Extension>,
+    /// Url of the page used to edit a chapter, with `{path}` standing for the
+    /// chapter's source path. Adds an edit link to the infobox header when set.
+    pub edit_url_template: Option<String>,
+    /// Hide field values longer than `collapsible-field-length` characters behind
+    /// a "Show more" toggle.
+    pub collapsible_fields: bool,
+    pub collapsible_field_length: usize,
+    /// Render yes/no and true/false field values as `true-icon`/`false-icon`.
+    pub boolean_icons: bool,
+    pub true_icon: String,
+    pub false_icon: String,
+    /// schema.org type, such as `Person`, to describe infoboxes with microdata.
+    pub schema_type: Option<String>,
+    /// Fields whose inline code is rendered as `<kbd>`, for keyboard shortcuts.
+    pub kbd_fields: Vec<String>,
+    /// Fields whose comma or bullet separated values are rendered as tags.
+    pub tag_fields: Vec<String>,
+    /// Fields whose percentage values, such as `75%`, are rendered as progress bars.
+    pub progress_fields: Vec<String>,
+    /// Cut field values longer than this many characters, showing the full value
+    /// as a tooltip.
+    pub max_field_length: Option<usize>,
+    /// Mark infobox images with `zoom-attribute` so a lightbox script can pick them up.
+    pub zoomable_images: bool,
+    pub zoom_attribute: String,
+    /// Link the infobox title to the infobox itself, like mdbook's header anchors.
+    pub title_anchor: bool,
+    /// Render a paragraph right after the title as a lead row, even when
+    /// `render-loose-content` is off.
+    pub lead_paragraph: bool,
+    /// How characters are escaped in generated html.
+    pub escape_style: EscapeStyle,
+    /// Write each rendered infobox to a handlebars partial in `partials-dir`,
+    /// leaving a `{{> name}}` placeholder in the chapter instead.
+    pub emit_partials: bool,
+    pub partials_dir: PathBuf,
+    /// Join numbers and the units after them, such as `5 kg`, with a non-breaking space.
+    pub nbsp_units: bool,
+    /// Add a JSON-LD `<script>` with the infobox's fields next to the table, for
+    /// search engines.
+    pub emit_jsonld: bool,
+    /// CSS `border` applied inline to infobox images, such as `1px solid #ccc`.
+    pub image_border: Option<String>,
+    /// CSS `box-shadow` applied inline to infobox images.
+    pub image_shadow: Option<String>,
+    /// Add a footer with the date the chapter was last updated, taken from a `date`
+    /// in its front matter or else the source file's modification time.
+    pub show_updated: bool,
+    /// Most images an infobox may have, and what happens to infoboxes with more.
+    pub max_images: Option<usize>,
+    pub max_images_behavior: LimitBehavior,
+    /// Show a small swatch of the color next to hex color values, such as `#ff8800`.
+    pub color_swatch: bool,
+    /// Html inserted verbatim as a footer row of every infobox, such as a disclaimer.
+    pub footer_html: Option<String>,
+    /// Warn about images inlined as `data:` urls longer than this many bytes.
+    pub max_data_uri_size: Option<usize>,
+    /// Named html templates, picked per infobox with `{{#infobox template-ref="name"}}`.
+    /// `{id}`, `{title}` and `{rows}` are replaced with the infobox's id, title and rows.
+    pub templates: HashMap<String, String>,
+    /// Turn `[[Page Name]]` and `[[Page Name|label]]` in field values into links to
+    /// the chapter at `page-name.md`.
+    pub wiki_links: bool,
+    /// Open the full resolution image linked from an infobox image in a new tab.
+    pub open_in_new_tab: bool,
+    /// Whether bold labels in a field's `**Label:** value` lines come before or after
+    /// their values.
+    pub field_order: FieldOrder,
+    /// Add links to the previous and next chapters of the book to the infobox footer.
+    pub nav_footer: bool,
+    /// Text that forces a line break in field values, such as ` \\ `. An inline `<br>`
+    /// always does.
+    pub line_break_marker: Option<String>,
+    /// Render `{漢字|かんじ}` in field values as ruby annotations.
+    pub ruby: bool,
+    /// Add thousands separators, following the book's language, to large numbers in
+    /// field values.
+    pub format_numbers: bool,
+    /// Show an image's alt text in its place when the image fails to load.
+    pub show_alt_fallback: bool,
+    /// Leave out fields whose value is empty or exactly `null-token`.
+    pub drop_empty_fields: bool,
+    pub null_token: Option<String>,
+    /// Number of header cells the title is split into, on `|`, for comparison layouts.
+    pub header_cells: usize,
+    /// CSS custom properties set inline on every infobox, such as
+    /// `infobox-accent = "#f80"`, for themes to pick up.
+    pub css_vars: BTreeMap<String, String>,
+    /// Render soft line breaks in field values as `<br>` instead of a space. Hard
+    /// breaks always are.
+    pub soft_break_as_br: bool,
+    /// Fields whose `N/M` values, such as `4/5`, are rendered as star ratings, for scales up to 10.
+    pub rating_fields: Vec<String>,
+    /// Leave an html comment saying why an infobox was skipped, such as
+    /// `<!-- infobox skipped: disabled -->`, where it was.
+    pub debug_comments: bool,
+    /// Prefix for the attributes, such as `data-`, that unknown `{{#infobox key="value"}}`
+    /// attributes are passed on to the table as.
+    pub attribute_prefix: String,
+    /// Wrap infoboxes in a `<details>`, summarized by their title, so readers can
+    /// collapse them.
+    pub collapsible: bool,
+    /// Fields rendered as a full-width row at the top of the infobox, wherever they are,
+    /// `Summary` and `TL;DR` by default.
+    pub summary_fields: Vec<String>,
+    /// Render images that have a caption as bordered figures, whatever `image-semantics` is.
+    pub framed_images: bool,
+    /// CSS class of the infobox table, in place of `infobox`.
+    pub class: String,
+    /// Name of the tags around infoboxes, `{{#infobox}}` and `{{/infobox}}` by default.
+    pub tag: String,
+    /// Side of the page infoboxes float to, letting the chapter's text wrap around them.
+    pub align: InfoboxAlign,
+    /// How infobox ids, used as anchors and partial names, are derived.
+    pub id_strategy: IdStrategy,
+    /// What infoboxes are rendered as.
+    pub layout: Layout,
+    /// Add the bundled stylesheet in a `<style>` to every chapter with infoboxes, for
+    /// books that don't include it through `additional-css`.
+    pub inline_css: bool,
+    /// Fail the build when infoboxes in different chapters share an id, rather than warn.
+    pub deny_duplicate_ids: bool,
+    /// The book's language, taken from `book.language` rather than this table.
+    #[serde(skip)]
+    pub language: Option<String>,
+    /// The book's root directory, taken from the preprocessor context.
+    #[serde(skip)]
+    pub book_root: PathBuf,
+    /// The directory chapter source paths are relative to.
+    #[serde(skip)]
+    pub src_dir: PathBuf,
+}
+
+impl Default for InfoboxConfig {
+    fn default() -> Self {
+        Self {
+            print_friendly: false,
+            divider_after_image: false,
+            sticky_header: false,
+            required_fields: Vec::new(),
+            image_title_fallback: ImageTitleFallback::default(),
+            trim_surrounding_blanks: false,
+            render_loose_content: false,
+            image_semantics: ImageSemantics::default(),
+            prefix_html: None,
+            suffix_html: None,
+            address_fields: vec!["Address".into()],
+            extensions: vec![
+                Extension::Tables,
+                Extension::Footnotes,
+                Extension::Strikethrough,
+                Extension::Tasklists,
+            ],
+            edit_url_template: None,
+            collapsible_fields: false,
+            collapsible_field_length: 200,
+            boolean_icons: false,
+            true_icon: "✓".into(),
+            false_icon: "✗".into(),
+            schema_type: None,
+            kbd_fields: Vec::new(),
+            tag_fields: Vec::new(),
+            progress_fields: Vec::new(),
+            max_field_length: None,
+            zoomable_images: false,
+            zoom_attribute: "data-zoomable".into(),
+            title_anchor: false,
+            lead_paragraph: false,
+            escape_style: EscapeStyle::default(),
+            emit_partials: false,
+            partials_dir: PathBuf::from("theme/partials"),
+            nbsp_units: false,
+            emit_jsonld: false,
+            image_border: None,
+            image_shadow: None,
+            show_updated: false,
+            max_images: None,
+            max_images_behavior: LimitBehavior::default(),
+            color_swatch: false,
+            footer_html: None,
+            max_data_uri_size: None,
+            templates: HashMap::new(),
+            wiki_links: false,
+            open_in_new_tab: false,
+            field_order: FieldOrder::default(),
+            nav_footer: false,
+            line_break_marker: None,
+            ruby: false,
+            format_numbers: false,
+            show_alt_fallback: false,
+            drop_empty_fields: false,
+            null_token: None,
+            header_cells: 1,
+            css_vars: BTreeMap::new(),
+            soft_break_as_br: false,
+            rating_fields: Vec::new(),
+            debug_comments: false,
+            attribute_prefix: "data-".into(),
+            collapsible: false,
+            summary_fields: vec!["Summary".into(), "TL;DR".into()],
+            framed_images: false,
+            class: "infobox".into(),
+            tag: "infobox".into(),
+            align: InfoboxAlign::default(),
+            id_strategy: IdStrategy::default(),
+            layout: Layout::default(),
+            inline_css: false,
+            deny_duplicate_ids: false,
+            language: None,
+            book_root: PathBuf::new(),
+            src_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl InfoboxConfig {
+    pub fn from_context(ctx: &PreprocessorContext) -> MdbookResult<Self> {
+        let mut config: Self = ctx.config
+            .get_deserialized_opt("preprocessor.infobox")?
+            .unwrap_or_default();
+        config.language = ctx.config.book.language.clone();
+        config.book_root = ctx.root.clone();
+        config.src_dir = ctx.root.join(&ctx.config.book.src);
+
+        Ok(config)
+    }
+
+    pub fn parser_options(&self) -> Options {
+        let mut parser_opts = Options::empty();
+        for extension in &self.extensions {
+            parser_opts.insert(match extension {
+                Extension::Tables => Options::ENABLE_TABLES,
+                Extension::Footnotes => Options::ENABLE_FOOTNOTES,
+                Extension::Strikethrough => Options::ENABLE_STRIKETHROUGH,
+                Extension::Tasklists => Options::ENABLE_TASKLISTS,
+                // Picked out of plain paragraphs while parsing infoboxes instead
+                Extension::DescriptionLists => Options::empty(),
+            });
+        }
+
+        parser_opts
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageTitleFallback {
+    /// Reuse the image's alt text as its title.
+    #[default]
+    Alt,
+    /// Leave the title empty.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageSemantics {
+    /// A bare `<img>`.
+    #[default]
+    Img,
+    /// An `<img>` inside a `<figure>`, captioned with the image's alt text.
+    Figure,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldOrder {
+    /// `**Name:** value`
+    #[default]
+    NameValue,
+    /// `value — **Name**`
+    ValueName,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InfoboxAlign {
+    /// Float right, like wiki infoboxes. This is the theme's default, so no class is added.
+    #[default]
+    Right,
+    /// Float left, with the `infobox-align-left` class.
+    Left,
+    /// Don't float, with the `infobox-align-none` class.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdStrategy {
+    /// The title, such as `infobox-sunshine`.
+    #[default]
+    Title,
+    /// A hash of the title and field names, such as `infobox-8a3f0c2d9e4b1a67`.
+    ContentHash,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// An html table.
+    #[default]
+    Html,
+    /// A two column markdown table, left for the renderer to style.
+    MarkdownTable,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LimitBehavior {
+    /// Fail the build.
+    #[default]
+    Error,
+    /// Drop whatever goes over the limit.
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extension {
+    Tables,
+    Footnotes,
+    Strikethrough,
+    Tasklists,
+    /// `Term` lines each followed by a `: Definition` line, rendered as fields.
+    DescriptionLists,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EscapeStyle {
+    /// Named entities, such as `&amp;`.
+    #[default]
+    Named,
+    /// Numeric character references, such as `&#38;`.
+    Numeric,
+}
@@ -0,0 +1,95 @@
+use crate::config::EscapeStyle;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+/// Escapes the characters that have a meaning in html text and attribute values.
+pub fn escape_html(text: &str, style: EscapeStyle) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        let entity = match (ch, style) {
+            ('&', EscapeStyle::Named) => "&amp;",
+            ('<', EscapeStyle::Named) => "&lt;",
+            ('>', EscapeStyle::Named) => "&gt;",
+            ('"', EscapeStyle::Named) => "&quot;",
+            ('\'', EscapeStyle::Named) => "&apos;",
+            ('&', EscapeStyle::Numeric) => "&#38;",
+            ('<', EscapeStyle::Numeric) => "&#60;",
+            ('>', EscapeStyle::Numeric) => "&#62;",
+            ('"', EscapeStyle::Numeric) => "&#34;",
+            ('\'', EscapeStyle::Numeric) => "&#39;",
+            _ => {
+                escaped.push(ch);
+                continue;
+            },
+        };
+
+        escaped.push_str(entity);
+    }
+
+    escaped
+}
+
+/// Decodes the named entities `escape_html` produces, plus `&nbsp;` and numeric entities.
+/// Unknown entities are left as written.
+pub fn unescape_html(text: &str) -> String {
+    lazy_static! {
+        static ref ENTITY_RE: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    };
+
+    ENTITY_RE.replace_all(text, |captures: &Captures| {
+        let entity = &captures[1];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()).and_then(char::from_u32),
+            },
+        };
+
+        decoded.map_or_else(|| captures[0].to_string(), String::from)
+    }).into_owned()
+}
+
+/// Turns rendered html back into plain text, dropping the tags and decoding the entities.
+pub fn html_to_text(html: &str) -> String {
+    lazy_static! {
+        static ref TAG_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+    };
+
+    unescape_html(&TAG_RE.replace_all(html, ""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape_html_named() {
+        assert_eq!("Tom &amp; Jerry", escape_html("Tom & Jerry", EscapeStyle::Named));
+        assert_eq!("&lt;b&gt; &quot;x&quot;", escape_html(r#"<b> "x""#, EscapeStyle::Named));
+    }
+
+    #[test]
+    fn test_escape_html_numeric() {
+        assert_eq!("Tom &#38; Jerry", escape_html("Tom & Jerry", EscapeStyle::Numeric));
+        assert_eq!("&#60;b&#62; &#34;x&#34;", escape_html(r#"<b> "x""#, EscapeStyle::Numeric));
+    }
+
+    #[test]
+    fn test_unescape_html() {
+        assert_eq!("Tom & Jerry", unescape_html("Tom &amp; Jerry"));
+        assert_eq!(r#"<b> "x" 'y'"#, unescape_html("&#60;b&#x3E; &quot;x&#34; &apos;y&#39;"));
+        assert_eq!("&copy; & more", unescape_html("&copy; & more"));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!("Tom & Jerry", html_to_text(r#"<strong>Tom</strong> &amp; <a href="j.md">Jerry</a>"#));
+    }
+}
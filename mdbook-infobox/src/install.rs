@@ -0,0 +1,40 @@
+use anyhow::Context;
+use mdbook::errors::Result as MdbookResult;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The infobox stylesheet, bundled with the preprocessor.
+pub const INFOBOX_CSS: &str = include_str!("../assets/infobox.css");
+
+/// Where the stylesheet is installed, relative to the book's root.
+pub const CSS_PATH: &str = "theme/css/infobox.css";
+
+/// Writes the bundled stylesheet into the book at `book_dir`, returning the path it
+/// was written to.
+pub fn install(book_dir: &Path) -> MdbookResult<PathBuf> {
+    let css_path = book_dir.join(CSS_PATH);
+    if let Some(css_dir) = css_path.parent() {
+        fs::create_dir_all(css_dir)
+            .with_context(|| format!("failed to create css directory {:?}", css_dir))?;
+    }
+
+    fs::write(&css_path, INFOBOX_CSS)
+        .with_context(|| format!("failed to write infobox stylesheet {:?}", css_path))?;
+
+    Ok(css_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_install() {
+        let book_dir = tempfile::tempdir().unwrap();
+
+        let css_path = install(book_dir.path()).unwrap();
+
+        assert_eq!(book_dir.path().join("theme/css/infobox.css"), css_path);
+        assert_eq!(INFOBOX_CSS, fs::read_to_string(css_path).unwrap());
+    }
+}
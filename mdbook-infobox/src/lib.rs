@@ -1,3 +1,9 @@
+pub mod config;
+pub mod escape;
+pub mod install;
 pub mod preprocessor;
+pub mod schema;
 
-pub use crate::preprocessor::InfoboxPreprocessor;
+pub use crate::config::InfoboxConfig;
+pub use crate::preprocessor::{infobox_ranges, infobox_ranges_with_tag, infobox_regex, Infobox, InfoboxDescription, InfoboxField, InfoboxGallery, InfoboxImage, InfoboxPreprocessor, InfoboxSection};
+pub use crate::schema::InfoboxSchema;
@@ -1,20 +1,34 @@
+use std::path::Path;
 use std::process::ExitCode;
 use std::{io, env};
 
 use mdbook::errors::Result as MdbookResult;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
 use mdbook_infobox::InfoboxPreprocessor;
+use mdbook_infobox::install::{install, CSS_PATH};
 
 fn main() -> MdbookResult<ExitCode> {
     let args: Vec<_> = env::args().collect();
     if let [_, command, argument] = &args[..] {
         if command == "supports" {
-            return Ok(match argument.as_str() {
-                "html" => ExitCode::SUCCESS,
-                _ => ExitCode::FAILURE
+            return Ok(if InfoboxPreprocessor.supports_renderer(argument) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
             });
         }
     }
+    if let [_, command, rest @ ..] = &args[..] {
+        if command == "install" && rest.len() <= 1 {
+            let book_dir = rest.first().map_or(".", String::as_str);
+            let css_path = install(Path::new(book_dir))?;
+
+            println!("Wrote {}", css_path.display());
+            println!("Add it to the book's book.toml:\n");
+            println!("[output.html]\nadditional-css = [\"{}\"]", CSS_PATH);
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
 
     let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
 
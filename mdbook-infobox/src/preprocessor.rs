@@ -1,14 +1,24 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use mdbook::BookItem;
-use mdbook::errors::{Result as MdbookResult, Error as MdbookError};
-use mdbook::book::Book;
+use mdbook::utils::fs::path_to_root;
+use mdbook::utils::normalize_id;
+use mdbook::errors::Result as MdbookResult;
+use mdbook::book::{Book, Chapter};
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
-use pulldown_cmark::{Options, Parser, Event, Tag};
+use pulldown_cmark::{CodeBlockKind, HeadingLevel, Options, Parser, Event, Tag};
 use regex::{Regex, Captures};
-use serde::ser::Impossible;
+use std::collections::HashMap;
+use std::fs;
 use std::iter::{Iterator, Peekable};
 use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::config::{Extension, FieldOrder, IdStrategy, ImageSemantics, InfoboxAlign, Layout, ImageTitleFallback, InfoboxConfig, LimitBehavior};
+use crate::escape::{escape_html, html_to_text};
+use crate::install::INFOBOX_CSS;
+use crate::schema::InfoboxSchema;
 
 pub struct InfoboxPreprocessor;
 
@@ -18,6 +28,18 @@ impl Preprocessor for InfoboxPreprocessor {
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> MdbookResult<Book> {
+        if !self.supports_renderer(&ctx.renderer) {
+            return Ok(book);
+        }
+
+        let config = InfoboxConfig::from_context(ctx)?;
+        // Other renderers only get infoboxes that are rendered as markdown
+        if ctx.renderer != "html" && config.layout != Layout::MarkdownTable {
+            return Ok(book);
+        }
+        let chapter_navs = if config.nav_footer { chapter_navs(&book) } else { HashMap::new() };
+        let infobox_re = infobox_regex(&config.tag);
+        let mut infobox_chapters: HashMap<String, Vec<String>> = HashMap::new();
         let mut error = None;
         book.for_each_mut(|section| {
             if error.is_some() {
@@ -25,146 +47,454 @@ impl Preprocessor for InfoboxPreprocessor {
             }
 
             if let BookItem::Chapter(ref mut ch) = *section {
-                let preprocessed_content = match preprocess_chapter(&ch.content) {
-                    Ok(content) => content,
+                let nav = ch.path.as_ref().and_then(|path| chapter_navs.get(path));
+                let preprocessed_content = preprocess_chapter(ch, &config, nav, &infobox_re)
+                    .with_context(|| format!("failed to preprocess chapter {:?}", ch.name));
+
+                let preprocessed_chapter = match preprocessed_content {
+                    Ok(preprocessed_chapter) => preprocessed_chapter,
                     Err(e) => {
                         error = Some(e);
                         return;
                     }
                 };
 
-                ch.content = preprocessed_content;
+                for id in preprocessed_chapter.infobox_ids {
+                    infobox_chapters.entry(id).or_default().push(ch.name.clone());
+                }
+
+                ch.content = preprocessed_chapter.content;
             };
         });
 
-        match error {
-            Some(e) => Err(e),
-            None => Ok(book)
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let mut collisions: Vec<_> = infobox_chapters.iter()
+            .filter(|(_, chapters)| chapters.len() > 1)
+            .map(|(id, chapters)| format!("{:?} ({})", id, chapters.join(", ")))
+            .collect();
+
+        if !collisions.is_empty() {
+            collisions.sort();
+            if config.deny_duplicate_ids {
+                return Err(anyhow!("duplicate infobox ids: {}", collisions.join("; ")));
+            }
+            eprintln!("Warning: duplicate infobox ids: {}", collisions.join("; "));
+        }
+
+        Ok(book)
+    }
+
+    fn supports_renderer(&self, renderer: &str) -> bool {
+        renderer == "html" || renderer == "markdown"
+    }
+}
+
+struct PreprocessedChapter {
+    content: String,
+    infobox_ids: Vec<String>,
+}
+
+/// The chapters before and after a chapter, in the order the book lists them.
+struct ChapterNav {
+    prev: Option<ChapterLink>,
+    next: Option<ChapterLink>,
+}
+
+#[derive(Clone)]
+struct ChapterLink {
+    name: String,
+    path: PathBuf,
+}
+
+fn chapter_navs(book: &Book) -> HashMap<PathBuf, ChapterNav> {
+    let chapters: Vec<_> = book.iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => ch.path.as_ref().map(|path| ChapterLink { name: ch.name.clone(), path: path.clone() }),
+            _ => None,
+        })
+        .collect();
+
+    chapters.iter()
+        .enumerate()
+        .map(|(i, chapter)| (chapter.path.clone(), ChapterNav {
+            prev: i.checked_sub(1).map(|prev| chapters[prev].clone()),
+            next: chapters.get(i + 1).cloned(),
+        }))
+        .collect()
+}
+
+fn preprocess_chapter(
+    chapter: &Chapter,
+    config: &InfoboxConfig,
+    nav: Option<&ChapterNav>,
+    infobox_re: &Regex,
+) -> MdbookResult<PreprocessedChapter> {
+    let content = &chapter.content;
+    let mut replacements = Vec::new();
+    let mut infobox_ids = Vec::new();
+
+    for InfoboxMatch { contents: infobox_contents, attributes, range } in find_infoboxes_contents(content, infobox_re) {
+        // Disabled infoboxes are left as plain markdown, without the infobox tags
+        if has_attribute(&attributes, "disabled") {
+            let contents = if config.debug_comments {
+                format!("\n<!-- infobox skipped: disabled -->{}", infobox_contents)
+            } else {
+                infobox_contents
+            };
+            replacements.push((range, contents));
+            continue;
+        }
+
+        let mut infobox = Infobox::from_markdown_content_with_config(&infobox_contents, config)?;
+        if config.drop_empty_fields {
+            infobox.retain_fields(|field| {
+                let value = field.contents.trim();
+                !value.is_empty() && config.null_token.as_deref() != Some(value)
+            });
+        }
+        infobox.check_required_fields(&config.required_fields)?;
+        if let Some(max_images) = config.max_images {
+            infobox.limit_images(max_images, config.max_images_behavior)?;
+        }
+        if let Some(max_data_uri_size) = config.max_data_uri_size {
+            infobox.warn_large_data_uris(max_data_uri_size, &chapter.name);
+        }
+        infobox_ids.push(infobox.id_with_config(config));
+
+        let mut html = match attribute_value(&attributes, "template-ref") {
+            Some(template_ref) => {
+                let template = config.templates.get(template_ref)
+                    .ok_or_else(|| anyhow!("unknown infobox template {:?}", template_ref))?;
+                infobox.render_template_html(template, config, Some(chapter))
+            },
+            None if config.layout == Layout::MarkdownTable => infobox.render_markdown_table(config, Some(chapter)),
+            None => infobox.render_chapter_html(config, Some(chapter), nav, &attributes),
+        };
+        if config.emit_partials {
+            html = write_partial(config, &infobox.id_with_config(config), &html)?;
         }
+
+        let (range, html) = if config.trim_surrounding_blanks {
+            trim_surrounding_blanks(content, range, html)
+        } else {
+            (range, html)
+        };
+
+        replacements.push((range, html));
     }
+
+    let mut content = replace_ranges(content, replacements);
+    // Every chapter is a page of its own, so each one with infoboxes gets the stylesheet
+    if config.inline_css && config.layout == Layout::Html && !infobox_ids.is_empty() {
+        content = format!("<style>\n{}</style>\n\n{}", INFOBOX_CSS, content);
+    }
+
+    Ok(PreprocessedChapter {
+        content,
+        infobox_ids,
+    })
 }
 
-fn preprocess_chapter(content: &str) -> MdbookResult<String> {
-    let mut output: String = content.into();
+/// Stitches `content` back together with each range, all relative to `content` and in
+/// order, swapped for its replacement.
+fn replace_ranges(content: &str, replacements: Vec<(Range<usize>, String)>) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut last_end = 0;
+
+    for (range, replacement) in replacements {
+        // Ranges extended over surrounding blank lines can overlap the previous one's,
+        // which already left a blank line behind it
+        let (start, replacement) = if range.start < last_end {
+            (last_end, replacement.trim_start_matches('\n'))
+        } else {
+            (range.start, replacement.as_str())
+        };
 
-    for (infobox_contents, range) in find_infoboxes_contents(content) {
-        let infobox = Infobox::from_markdown_content(&infobox_contents)?;
-        output.replace_range(range, &infobox.render_html());
+        output.push_str(&content[last_end..start]);
+        output.push_str(replacement);
+        last_end = range.end;
     }
+    output.push_str(&content[last_end..]);
+
+    output
+}
+
+/// Writes a rendered infobox to a theme partial, returning the placeholder that
+/// includes it.
+fn write_partial(config: &InfoboxConfig, name: &str, html: &str) -> MdbookResult<String> {
+    let partials_dir = config.book_root.join(&config.partials_dir);
+    fs::create_dir_all(&partials_dir)
+        .with_context(|| format!("failed to create partials directory {:?}", partials_dir))?;
+
+    let partial_path = partials_dir.join(format!("{}.hbs", name));
+    fs::write(&partial_path, html)
+        .with_context(|| format!("failed to write infobox partial {:?}", partial_path))?;
+
+    Ok(format!("{{{{> {}}}}}", name))
+}
+
+/// Extends `range` over the blank lines around it and pads `html` so that it ends up
+/// separated from the rest of the chapter by exactly one blank line.
+fn trim_surrounding_blanks(content: &str, range: Range<usize>, html: String) -> (Range<usize>, String) {
+    let start = content[..range.start].trim_end().len();
+    let end = content.len() - content[range.end..].trim_start().len();
 
-    Ok(output)
+    let leading = if start == 0 { "" } else { "\n\n" };
+    let trailing = if end == content.len() { "\n" } else { "\n\n" };
+
+    (start..end, format!("{}{}{}", leading, html, trailing))
 }
 
 type MarkdownContents = String;
+type EventIter<'a> = Peekable<std::vec::IntoIter<(Event<'a>, Range<usize>)>>;
 
 #[derive(Debug, PartialEq, Eq)]
-struct Infobox {
-    title: String,
-    sections: Vec<InfoboxSection>,
+pub struct Infobox {
+    /// Plain text of the title, used for its id and in messages.
+    pub title: String,
+    /// The title rendered with its inline formatting, such as emphasis.
+    pub title_html: String,
+    pub sections: Vec<InfoboxSection>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
-enum InfoboxSection {
+pub enum InfoboxSection {
     Image(InfoboxImage),
+    Gallery(InfoboxGallery),
     Field(InfoboxField),
+    Description(InfoboxDescription),
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct InfoboxImage {
-    url: String,
-    title: Option<String>,
+pub struct InfoboxImage {
+    pub url: String,
+    pub alt: Option<String>,
+    pub title: Option<String>,
+    pub srcset: Option<String>,
+    pub aspect_ratio: Option<String>,
+    /// Url of the full resolution image the rendered image links to.
+    pub full: Option<String>,
+    /// Size in pixels the image is displayed at.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Markup for this image, overriding `image-semantics`.
+    pub semantics: Option<ImageSemantics>,
+    /// Byte range of the image within the infobox contents.
+    pub range: Range<usize>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct InfoboxField {
-    name: String,
-    contents: MarkdownContents,
+pub struct InfoboxField {
+    pub name: String,
+    pub contents: MarkdownContents,
+    /// Text alignment of the value, from an `{align=right}` hint after the name.
+    pub align: Option<String>,
+    /// Text direction of the value, from a `{dir=rtl}` hint after the name.
+    pub dir: Option<String>,
+    /// Byte range of the field, from its heading to the end of its contents,
+    /// within the infobox contents.
+    pub range: Range<usize>,
 }
 
-fn find_infoboxes_contents(content: &str) -> Vec<(String, Range<usize>)> {
-    lazy_static! {
-        static ref RE: Regex = {
-            Regex::new(
-                r"(?xms)              # insignificant whitespace/multiline/dot matches newline mode
-            \{\{\#infobox\}\}      # infobox opening tag
-            (.*)                    # infobox contents
-            \{\{/infobox\}\}      # infobox closing tag",
-            )
-            .unwrap()
-        };
-    };
+/// Images that follow each other without a field in between, shown side by side.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InfoboxGallery {
+    pub images: Vec<InfoboxImage>,
+    /// Byte range of the images within the infobox contents.
+    pub range: Range<usize>,
+}
+
+/// Prose found outside of any field, such as a paragraph between the title and the
+/// first field.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InfoboxDescription {
+    pub contents: MarkdownContents,
+    /// Byte range of the prose within the infobox contents.
+    pub range: Range<usize>,
+}
+
+/// Attributes given in the opening tag, e.g. `{{#infobox disabled key="value"}}`.
+/// Flags have no value.
+type InfoboxAttributes = Vec<(String, Option<String>)>;
+
+/// Attributes the preprocessor acts on itself, anything else is passed on to the table.
+const KNOWN_ATTRIBUTES: &[&str] = &["disabled", "template-ref"];
+
+struct InfoboxMatch {
+    contents: String,
+    attributes: InfoboxAttributes,
+    range: Range<usize>,
+}
+
+/// Byte ranges of every infobox in a chapter, from the opening to the closing tag.
+pub fn infobox_ranges(content: &str) -> Vec<Range<usize>> {
+    infobox_ranges_with_tag(content, DEFAULT_TAG)
+}
 
-    RE.captures_iter(content)
+/// Byte ranges of every infobox in a chapter, for infoboxes tagged `{{#tag}}...{{/tag}}`.
+pub fn infobox_ranges_with_tag(content: &str, tag: &str) -> Vec<Range<usize>> {
+    find_infoboxes_contents(content, &infobox_regex(tag))
+        .into_iter()
+        .map(|infobox_match| infobox_match.range)
+        .collect()
+}
+
+const DEFAULT_TAG: &str = "infobox";
+
+/// Matches infoboxes opened with `{{#tag}}` and closed with `{{/tag}}`, capturing their
+/// `attributes` and `contents`, as `Infobox::from_capture` expects.
+pub fn infobox_regex(tag: &str) -> Regex {
+    let tag = regex::escape(tag);
+
+    Regex::new(&format!(
+        r"(?xms)              # insignificant whitespace/multiline/dot matches newline mode
+        \{{\{{\#{tag}                   # infobox opening tag
+        (?:\s+(?P<attributes>[^}}]*))? # infobox attributes, set apart from the tag by whitespace
+        \}}\}}
+        (?P<contents>.*?)              # infobox contents
+        \{{\{{/{tag}\}}\}}               # infobox closing tag",
+        tag = tag,
+    ))
+    .unwrap()
+}
+
+fn find_infoboxes_contents(content: &str, infobox_re: &Regex) -> Vec<InfoboxMatch> {
+    infobox_re.captures_iter(content)
         .map(|capture| {
             let full_match = capture.get(0).unwrap();
-            let infobox_contents_match = capture.get(1).unwrap();
 
-            (infobox_contents_match.as_str().to_owned(), full_match.range())
+            InfoboxMatch {
+                contents: capture["contents"].to_owned(),
+                attributes: parse_attributes(capture.name("attributes").map_or("", |attributes| attributes.as_str())),
+                range: full_match.range(),
+            }
         })
         .collect()
 }
 
+fn parse_attributes(attributes: &str) -> InfoboxAttributes {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r#"([\w-]+)(?:="([^"]*)")?"#).unwrap();
+    };
+
+    RE.captures_iter(attributes)
+        .map(|capture| (capture[1].to_owned(), capture.get(2).map(|value| value.as_str().to_owned())))
+        .collect()
+}
+
+fn has_attribute(attributes: &InfoboxAttributes, name: &str) -> bool {
+    attributes.iter().any(|(key, _)| key == name)
+}
+
+fn attribute_value<'a>(attributes: &'a InfoboxAttributes, name: &str) -> Option<&'a str> {
+    attributes.iter()
+        .find(|(key, _)| key == name)
+        .and_then(|(_, value)| value.as_deref())
+}
+
 impl Infobox {
+    /// Parses the contents of an infobox matched by the infobox tags regex.
     pub fn from_capture(capture: Captures<'_>) -> MdbookResult<Self> {
-        let content = capture.get(1).ok_or(anyhow!("could not find infobox contents"))?;
+        let content = capture.name("contents").ok_or(anyhow!("could not find infobox contents"))?;
 
-        todo!()
+        Self::from_markdown_content(content.as_str())
     }
 
     pub fn from_markdown_content(content: &str) -> MdbookResult<Self> {
-        let mut parser_opts = Options::empty();
-        parser_opts.insert(Options::ENABLE_TABLES);
-        parser_opts.insert(Options::ENABLE_FOOTNOTES);
-        parser_opts.insert(Options::ENABLE_STRIKETHROUGH);
-        parser_opts.insert(Options::ENABLE_TASKLISTS);
+        Self::from_markdown_content_with_options(content, InfoboxConfig::default().parser_options())
+    }
+
+    pub fn from_markdown_content_with_options(content: &str, parser_opts: Options) -> MdbookResult<Self> {
+        Self::parse(content, parser_opts, &InfoboxConfig::default())
+    }
+
+    /// Parses an infobox with the markdown extensions, escaping and field order from `config`.
+    pub fn from_markdown_content_with_config(content: &str, config: &InfoboxConfig) -> MdbookResult<Self> {
+        Self::parse(content, config.parser_options(), config)
+    }
 
+    fn parse(content: &str, parser_opts: Options, config: &InfoboxConfig) -> MdbookResult<Self> {
         let parser = Parser::new_ext(content, parser_opts);
-        let mut iter = parser.into_iter().peekable();
-        let title = Self::parse_infobox_title(&mut iter)?;
+        let mut events: Vec<_> = parser.into_offset_iter().collect();
+        if config.extensions.contains(&Extension::DescriptionLists) {
+            events = expand_description_lists(events);
+        }
+        let mut iter = events.into_iter().peekable();
+        let (title, title_html, title_level) = Self::parse_infobox_title(&mut iter, config)?;
         let mut sections = Vec::new();
 
-        while let Some(section) = Self::parse_infobox_section(&mut iter)? {
-            sections.push(section);
+        while let Some(parsed_sections) = Self::parse_infobox_sections(&mut iter, title_level, config)? {
+            sections.extend(parsed_sections);
         }
 
         Ok(Self {
             title,
+            title_html,
             sections
         })
     }
 
-    fn parse_infobox_title(iter: &mut Peekable<Parser>) -> MdbookResult<String> {
-        let mut title_heading_started = false;
+    fn parse_infobox_title(iter: &mut EventIter, config: &InfoboxConfig) -> MdbookResult<(String, String, HeadingLevel)> {
+        let mut title_level = None;
         let mut title_contents: String = String::new();
+        let mut title_html = String::new();
 
-        for event in iter {
-            if let Event::Start(Tag::Heading(_, _, _)) = event {
-                title_heading_started = true;
+        for (event, _) in iter {
+            if let Event::Start(Tag::Heading(level, _, _)) = event {
+                title_level = Some(level);
                 continue;
             }
             
-            if title_heading_started {
-                if let Event::Text(text) = event {
-                    title_contents += text.into_string().as_str();
-                    
+            if let Some(title_level) = title_level {
+                // Inline html in a title is kept as text
+                if let Event::Text(text) | Event::Code(text) | Event::Html(text) = &event {
+                    title_contents += text;
+                }
+
+                if let Event::End(Tag::Heading(_, _, _)) = event {
+                    return Ok((title_contents, title_html, title_level));
+                } else if let Event::Html(html) = &event {
+                    title_html += &escape_html(html, config.escape_style);
+                    continue;
+                } else if let Some(html) = render_inline_html(&event, config) {
+                    title_html += &html;
                     continue;
-                } else if let Event::End(Tag::Heading(_, _, _)) = event {
-                    return Ok(title_contents);
                 }
             }
             
             return Err(anyhow!("unexpected event: {:?}", event));
         }
 
-        return Err(anyhow!("failed to find infobox title"));
+        Err(anyhow!("failed to find infobox title"))
     }
 
-    fn parse_infobox_section(iter: &mut Peekable<Parser>) -> MdbookResult<Option<InfoboxSection>> {
-        while let Some(event) = iter.peek() {
-            if let Event::Start(Tag::Heading(_, _, _)) = event {
-                return Ok(Some(InfoboxSection::Field(Self::parse_infobox_field(iter)?)));
+    fn parse_infobox_sections(
+        iter: &mut EventIter,
+        title_level: HeadingLevel,
+        config: &InfoboxConfig,
+    ) -> MdbookResult<Option<Vec<InfoboxSection>>> {
+        while let Some((event, _)) = iter.peek() {
+            if let Event::Start(Tag::Heading(level, _, _)) = event {
+                if *level < title_level {
+                    return Err(anyhow!(
+                        "field heading ({}) is shallower than the infobox title heading ({}); \
+                        the title must use a heading at the same or a shallower level than its fields",
+                        level, title_level,
+                    ));
+                }
+                return Ok(Some(vec![InfoboxSection::Field(Self::parse_infobox_field(iter, config)?)]));
             } else if let Event::Start(Tag::Image(_, _, _)) = event {
-                return Ok(Some(InfoboxSection::Image(Self::parse_infobox_image(iter)?)));
+                return Ok(Some(vec![Self::parse_infobox_images(iter)?]));
+            } else if is_csv_block_start(event) {
+                let fields = Self::parse_infobox_csv_fields(iter, config)?;
+                return Ok(Some(fields.into_iter().map(InfoboxSection::Field).collect()));
+            } else if std::matches!(event, Event::Text(_) | Event::Code(_) | Event::Start(
+                Tag::Link(..) | Tag::Strong | Tag::Emphasis | Tag::Strikethrough
+            )) {
+                return Ok(Some(vec![InfoboxSection::Description(Self::parse_infobox_description(iter, config))]));
             }
 
             iter.next();
@@ -173,14 +503,16 @@ impl Infobox {
         Ok(None)
     }
 
-    fn parse_infobox_field(iter: &mut Peekable<Parser>) -> MdbookResult<InfoboxField> {
+    fn parse_infobox_field(iter: &mut EventIter, config: &InfoboxConfig) -> MdbookResult<InfoboxField> {
         // Parse name
         let mut name_heading_started = false;
         let mut name_contents = String::new();
+        let mut range = 0..0;
 
-        for event in &mut *iter {
+        for (event, event_range) in &mut *iter {
             if let Event::Start(Tag::Heading(_, _, _)) = event {
                 name_heading_started = true;
+                range = event_range;
                 continue;
             }
             
@@ -198,198 +530,3620 @@ impl Infobox {
         }
 
         let mut contents = String::new();
+        let mut body = Vec::new();
+        let mut in_diagram_block = false;
+        let mut in_note_block = false;
+        let mut in_code_block = false;
+        let mut in_table_head = false;
+        let mut paragraph_start = 0;
+        let mut paragraphs = Vec::new();
 
-        // Parse contents
-        while let Some(event) = iter.peek() {
-            // Reached another heading, finish parsing the field
+        // Parse contents, with owned events so that images can look ahead through the field
+        while let Some((event, event_range)) = iter.peek().cloned() {
+            // Reached another heading or a csv block, finish parsing the field
             if let Event::Start(Tag::Heading(_, _, _)) = event {
                 break;
+            } else if is_csv_block_start(&event) {
+                break;
+            }
+
+            // Images on their own lines at the end of the value start a new image section,
+            // whether or not a blank line separates them from it
+            let starts_line = std::matches!(
+                body.last(),
+                Some(Event::Start(Tag::Paragraph) | Event::SoftBreak | Event::HardBreak)
+            );
+            if std::matches!(event, Event::Start(Tag::Image(..)))
+                && starts_line
+                && !contents.trim().is_empty()
+                && only_images_left_in_field(iter)
+            {
+                let line_break_len = ["\n", "<br>"].iter()
+                    .find(|line_break| contents.ends_with(*line_break))
+                    .map_or(0, |line_break| line_break.len());
+                contents.truncate(contents.len() - line_break_len);
+                break;
             }
 
-            if let Event::Text(text) = event {
-                contents += text.to_string().as_str();
+            match &event {
+                // Diagram blocks are passed on as markdown, everything else ends up as html
+                Event::Text(text) if in_diagram_block => contents += text,
+                // A blank line would end the html block the infobox is rendered in, so code
+                // and notes keep their lines as character references
+                Event::Text(text) if in_code_block || in_note_block => {
+                    contents += &escape_html(text, config.escape_style).replace('\n', "&#10;");
+                },
+                // Soft breaks stay newlines, which show as spaces outside of address fields
+                Event::SoftBreak if config.soft_break_as_br => contents += "<br>",
+                Event::SoftBreak => contents += "\n",
+                Event::HardBreak => contents += "<br>",
+                Event::Start(Tag::Paragraph) => paragraph_start = contents.len(),
+                Event::End(Tag::Paragraph) => paragraphs.push(paragraph_start..contents.len()),
+                Event::Start(Tag::List(None)) => contents += "<ul>",
+                Event::Start(Tag::List(Some(1))) => contents += "<ol>",
+                Event::Start(Tag::List(Some(start))) => contents += format!(r##"<ol start="{}">"##, start).as_str(),
+                Event::End(Tag::List(None)) => contents += "</ul>",
+                Event::End(Tag::List(Some(_))) => contents += "</ol>",
+                Event::Start(Tag::BlockQuote) => contents += "<blockquote>",
+                Event::End(Tag::BlockQuote) => contents += "</blockquote>",
+                Event::Start(Tag::Item) => contents += "<li>",
+                Event::End(Tag::Item) => contents += "</li>",
+                // pulldown-cmark puts the header cells straight in the head, without a row
+                Event::Start(Tag::Table(_)) => contents += "<table>",
+                Event::End(Tag::Table(_)) => contents += "</tbody></table>",
+                Event::Start(Tag::TableHead) => {
+                    in_table_head = true;
+                    contents += "<thead><tr>";
+                },
+                Event::End(Tag::TableHead) => {
+                    in_table_head = false;
+                    contents += "</tr></thead><tbody>";
+                },
+                Event::Start(Tag::TableRow) => contents += "<tr>",
+                Event::End(Tag::TableRow) => contents += "</tr>",
+                Event::Start(Tag::TableCell) => contents += if in_table_head { "<th>" } else { "<td>" },
+                Event::End(Tag::TableCell) => contents += if in_table_head { "</th>" } else { "</td>" },
+                // Diagram blocks are kept as markdown, set apart from the surrounding html by
+                // blank lines, so that preprocessors like mdbook-mermaid still pick them up
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if is_diagram_language(lang) => {
+                    in_diagram_block = true;
+                    contents += format!("\n\n```{}\n", lang).as_str();
+                },
+                Event::End(Tag::CodeBlock(_)) if in_diagram_block => {
+                    in_diagram_block = false;
+                    contents += "```\n\n";
+                },
+                // Notes are inset within the value, their text kept as is
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if lang.as_ref() == "note" => {
+                    in_note_block = true;
+                    contents += r##"<div class="infobox-note">"##;
+                },
+                Event::End(Tag::CodeBlock(_)) if in_note_block => {
+                    in_note_block = false;
+                    contents.truncate(contents.trim_end_matches("&#10;").len());
+                    contents += "</div>";
+                },
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if !lang.is_empty() => {
+                    in_code_block = true;
+                    contents += format!(r##"<pre><code class="language-{}">"##, escape_html(lang, config.escape_style)).as_str();
+                },
+                Event::Start(Tag::CodeBlock(_)) => {
+                    in_code_block = true;
+                    contents += "<pre><code>";
+                },
+                Event::End(Tag::CodeBlock(_)) => {
+                    in_code_block = false;
+                    contents += "</code></pre>";
+                },
+                event => contents += &render_inline_html(event, config).unwrap_or_default(),
             }
 
+            body.push(event.clone());
+            range.end = range.end.max(event_range.end);
             iter.next();
         }
 
+        // A single paragraph is left bare, several are set apart as paragraphs
+        if paragraphs.len() > 1 {
+            for paragraph in paragraphs.iter().rev() {
+                contents.insert_str(paragraph.end, "</p>");
+                contents.insert_str(paragraph.start, "<p>");
+            }
+        }
+
+        if let Some(definition_list) = render_definition_list(&body, config) {
+            contents = definition_list;
+        }
+
+        let (name, hints) = parse_field_hints(&name_contents);
+
         Ok(InfoboxField {
-            name: name_contents,
+            name,
             contents,
+            align: hints.align,
+            dir: hints.dir,
+            range,
         })
     }
 
-    fn parse_infobox_image(iter: &mut Peekable<Parser>) -> MdbookResult<InfoboxImage> {
+    fn parse_infobox_description(iter: &mut EventIter, config: &InfoboxConfig) -> InfoboxDescription {
+        let mut contents = String::new();
+        let mut range: Option<Range<usize>> = None;
+
+        while let Some((event, event_range)) = iter.peek() {
+            match event {
+                Event::SoftBreak | Event::HardBreak => contents += " ",
+                event => match render_inline_html(event, config) {
+                    Some(html) => contents += &html,
+                    None => break,
+                },
+            }
+
+            let range = range.get_or_insert(event_range.clone());
+            range.end = event_range.end;
+            iter.next();
+        }
+
+        InfoboxDescription {
+            contents,
+            range: range.unwrap_or_default(),
+        }
+    }
+
+    fn parse_infobox_csv_fields(iter: &mut EventIter, config: &InfoboxConfig) -> MdbookResult<Vec<InfoboxField>> {
+        let range = match iter.next() {
+            Some((event, range)) if is_csv_block_start(&event) => range,
+            event => return Err(anyhow!("unexpected event {:?}", event.map(|(event, _)| event))),
+        };
+
+        let mut csv = String::new();
+        for (event, _) in &mut *iter {
+            match event {
+                Event::Text(text) => csv += &text,
+                Event::End(Tag::CodeBlock(_)) => break,
+                event => return Err(anyhow!("unexpected event: {:?}", event)),
+            }
+        }
+
+        // Each row is a `name,value` pair, values may contain further commas
+        csv.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let (name, contents) = line.split_once(',')
+                    .ok_or(anyhow!("invalid csv field row: {:?}", line))?;
+
+                Ok(InfoboxField {
+                    name: name.trim().into(),
+                    contents: escape_html(contents.trim(), config.escape_style),
+                    align: None,
+                    dir: None,
+                    range: range.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Parses an image, along with any images right after it as a gallery.
+    fn parse_infobox_images(iter: &mut EventIter) -> MdbookResult<InfoboxSection> {
+        let mut images = vec![Self::parse_infobox_image(iter)?];
+        loop {
+            match iter.peek() {
+                Some((Event::Start(Tag::Image(..)), _)) => images.push(Self::parse_infobox_image(iter)?),
+                Some((Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph) | Event::SoftBreak | Event::HardBreak, _)) => {
+                    iter.next();
+                },
+                _ => break,
+            }
+        }
+
+        if images.len() == 1 {
+            return Ok(InfoboxSection::Image(images.remove(0)));
+        }
+
+        let range = images[0].range.start..images[images.len() - 1].range.end;
+        Ok(InfoboxSection::Gallery(InfoboxGallery { images, range }))
+    }
+
+    fn parse_infobox_image<'a, I>(iter: &mut Peekable<I>) -> MdbookResult<InfoboxImage>
+    where
+        I: Iterator<Item = (Event<'a>, Range<usize>)>,
+    {
         match iter.next() {
-            Some(Event::Start(Tag::Image(_, url, _))) => {
+            Some((Event::Start(Tag::Image(_, url, title)), range)) => {
                 // For some reason titles come with a text after the thing?
-                let title = if let Some(Event::Text(title)) = iter.peek() {
-                    Some(title.to_string())
-                } else {
-                    None
-                };
-
-                if title.is_some() {
+                let mut alt_text = String::new();
+                while let Some((Event::Text(text), _)) = iter.peek() {
+                    alt_text += text;
                     iter.next();
                 }
 
-                assert!(std::matches!(iter.next(), Some(Event::End(Tag::Image(..)))));
+                match iter.next() {
+                    Some((Event::End(Tag::Image(..)), _)) => {},
+                    event => return Err(anyhow!(
+                        "malformed image {:?}: expected the end of the image, found {:?}",
+                        url.as_ref(),
+                        event.map(|(event, _)| event),
+                    )),
+                }
+
+                // Options come after the alt text, separated by pipes: ![alt|srcset=...](url)
+                let mut parts = alt_text.split('|');
+                let alt = parts.next()
+                    .map(str::trim)
+                    .filter(|alt| !alt.is_empty())
+                    .map(String::from);
+                let title = Some(title.to_string()).filter(|title| !title.is_empty());
+
+                let mut image = InfoboxImage {
+                    url: url.to_string(),
+                    alt,
+                    title,
+                    srcset: None,
+                    aspect_ratio: None,
+                    full: None,
+                    width: None,
+                    height: None,
+                    semantics: None,
+                    range,
+                };
+                for option in parts {
+                    image.set_option(option)?;
+                }
 
-                Ok(InfoboxImage { url: url.to_string(), title })
+                Ok(image)
             },
-            event => Err(anyhow!("unexpected event {:?}", event)),
+            event => Err(anyhow!("unexpected event {:?}", event.map(|(event, _)| event))),
         }
     }
 
-    fn render_html(&self) -> String {
-        let mut lines = vec![
-            r##"<table class="infobox">"##.into(),
-            "<thead>".into(),
-            "<tr>".into(),
-            format!(r##"<th colspan="2">{}</th>"##, self.title),
-            "</tr>".into(),
-            "</thead>".into(),
-        ];
+    /// Anchor id of the infobox, derived from its title.
+    pub fn id(&self) -> String {
+        format!("infobox-{}", normalize_id(&self.title))
+    }
 
-        for section in &self.sections {
-            lines.push(section.render_html())
+    /// Anchor id of the infobox, derived following `id-strategy`.
+    pub fn id_with_config(&self, config: &InfoboxConfig) -> String {
+        match config.id_strategy {
+            IdStrategy::Title => self.id(),
+            IdStrategy::ContentHash => format!("infobox-{:016x}", self.content_hash()),
         }
+    }
 
-        lines.push("</table>".into());
+    /// 64-bit FNV-1a hash of the title and field names, which stays the same from one
+    /// build, and Rust version, to the next.
+    fn content_hash(&self) -> u64 {
+        let field_names = self.sections.iter().filter_map(|section| match section {
+            InfoboxSection::Field(field) => Some(field.name.as_str()),
+            _ => None,
+        });
 
-        lines.join("\n")
+        std::iter::once(self.title.as_str())
+            .chain(field_names)
+            .flat_map(|text| text.bytes().chain(std::iter::once(0)))
+            .fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
     }
-}
 
-impl InfoboxSection {
-    pub fn render_html(&self) -> String {
-        match &self {
-            Self::Field(field) => Self::render_field_html(field),
-            Self::Image(image) => Self::render_image_html(image),
-        }
+    /// Every image, whether on its own or in a gallery.
+    fn images(&self) -> impl Iterator<Item = &InfoboxImage> {
+        self.sections.iter().flat_map(|section| match section {
+            InfoboxSection::Image(image) => std::slice::from_ref(image),
+            InfoboxSection::Gallery(gallery) => gallery.images.as_slice(),
+            _ => &[],
+        })
     }
 
-    fn render_field_html(field: &InfoboxField) -> String {
-        format!(r##"
-<tr>
-    <td>{}</td>
-    <td>{}</td>
-</tr>"##, field.name, field.contents)
+    /// Number of images and field sections, in that order.
+    pub fn counts(&self) -> (usize, usize) {
+        self.sections.iter().fold((0, 0), |(images, fields), section| match section {
+            InfoboxSection::Image(_) => (images + 1, fields),
+            InfoboxSection::Gallery(gallery) => (images + gallery.images.len(), fields),
+            InfoboxSection::Field(_) => (images, fields + 1),
+            InfoboxSection::Description(_) => (images, fields),
+        })
     }
 
-    fn render_image_html(image: &InfoboxImage) -> String {
-        format!(r##"
-<tr>
-    <td colspan="2"><img src="{}" title="{}"/></td>
-</tr>"##, image.url, image.title.clone().unwrap_or_default())
+    /// Keeps only the fields for which `f` returns true, other sections are left as is.
+    pub fn retain_fields<F: Fn(&InfoboxField) -> bool>(&mut self, f: F) {
+        self.sections.retain(|section| match section {
+            InfoboxSection::Field(field) => f(field),
+            _ => true,
+        });
     }
-}
 
-#[cfg(test)]
-mod test {
-    use serde_json::json;
+    /// Replaces every field with the result of `f`, other sections are left as is.
+    pub fn map_fields<F: Fn(InfoboxField) -> InfoboxField>(&mut self, f: F) {
+        self.sections = std::mem::take(&mut self.sections)
+            .into_iter()
+            .map(|section| match section {
+                InfoboxSection::Field(field) => InfoboxSection::Field(f(field)),
+                section => section,
+            })
+            .collect();
+    }
 
-    use super::*;
+    fn check_required_fields(&self, required_fields: &[String]) -> MdbookResult<()> {
+        for required_field in required_fields {
+            let has_field = self.sections.iter().any(|section| {
+                std::matches!(section, InfoboxSection::Field(field) if &field.name == required_field)
+            });
 
-    #[test]
-    fn test_find_infoboxes_contents() {
-        let document = r##"
-# Sunshine
-{{#infobox}}
-# Infobox title
-## Infobox field
-Field contents
-{{/infobox}}
+            if !has_field {
+                return Err(anyhow!("infobox {:?} is missing required field {:?}", self.title, required_field));
+            }
+        }
 
-# Description
-"##;
-        let expected_infobox_contents = r##"
-# Infobox title
-## Infobox field
-Field contents
-"##;
+        Ok(())
+    }
 
-        let infoboxes_contents = find_infoboxes_contents(document);
-        assert_eq!(1, infoboxes_contents.len());
+    /// Checks that the infobox has the schema's required fields, no fields outside of it,
+    /// and images only if the schema allows them.
+    pub fn validate(&self, schema: &InfoboxSchema) -> MdbookResult<()> {
+        self.check_required_fields(&schema.required_fields)?;
 
-        let (infobox_contents, _) = &infoboxes_contents[0];
+        for section in &self.sections {
+            match section {
+                InfoboxSection::Field(field) if !schema.allows_field(&field.name) => {
+                    return Err(anyhow!("infobox {:?} has unexpected field {:?}", self.title, field.name));
+                },
+                InfoboxSection::Image(_) | InfoboxSection::Gallery(_) if !schema.allow_images => {
+                    return Err(anyhow!("infobox {:?} has an image, which its schema doesn't allow", self.title));
+                },
+                _ => {},
+            }
+        }
 
-        assert_eq!(expected_infobox_contents, infobox_contents);
+        Ok(())
     }
 
-    #[test]
-    fn test_from_markdown_contents_simple() {
-        let infobox_contents = r##"
-# Sunshine
-## Name
-Testing
+    /// Errors or drops the images after the first `max_images`, depending on `behavior`.
+    fn limit_images(&mut self, max_images: usize, behavior: LimitBehavior) -> MdbookResult<()> {
+        let (images, _) = self.counts();
+        if images <= max_images {
+            return Ok(());
+        }
 
-## Birthday
-1999-07-27
+        match behavior {
+            LimitBehavior::Error => Err(anyhow!(
+                "infobox {:?} has {} images, more than the maximum of {}", self.title, images, max_images
+            )),
+            LimitBehavior::Truncate => {
+                let mut kept_images = 0;
+                self.sections.retain_mut(|section| match section {
+                    InfoboxSection::Image(_) => {
+                        kept_images += 1;
+                        kept_images <= max_images
+                    },
+                    InfoboxSection::Gallery(gallery) => {
+                        gallery.images.truncate(max_images.saturating_sub(kept_images));
+                        kept_images += gallery.images.len();
+                        !gallery.images.is_empty()
+                    },
+                    _ => true,
+                });
 
-## Age
-23 years
-"##;
+                Ok(())
+            },
+        }
+    }
 
-        let expected_infobox = Infobox {
-            title: "Sunshine".into(),
-            sections: vec![
-                InfoboxSection::Field(InfoboxField { name: "Name".into(), contents: "Testing".into() }),
-                InfoboxSection::Field(InfoboxField { name: "Birthday".into(), contents: "1999-07-27".into() }),
-                InfoboxSection::Field(InfoboxField { name: "Age".into(), contents: "23 years".into() }),
-            ],
-        };
+    fn warn_large_data_uris(&self, max_size: usize, chapter_name: &str) {
+        for image in self.images() {
+            if image.url.starts_with("data:") && image.url.len() > max_size {
+                eprintln!(
+                    "Warning: infobox {:?} in {:?} inlines a {} byte image, more than the maximum of {}",
+                    self.title, chapter_name, image.url.len(), max_size,
+                );
+            }
+        }
+    }
 
-        assert_eq!(expected_infobox, Infobox::from_markdown_content(infobox_contents).unwrap());
+    pub fn render_html(&self, config: &InfoboxConfig) -> String {
+        self.render_chapter_html(config, None, None, &InfoboxAttributes::new())
     }
 
-    #[test]
-    fn test_from_markdown_contents_with_image() {
-        let infobox_contents = r##"
-# Sunshine
-![image](images/test.jpg)
+    fn render_chapter_html(
+        &self,
+        config: &InfoboxConfig,
+        chapter: Option<&Chapter>,
+        nav: Option<&ChapterNav>,
+        infobox_attributes: &InfoboxAttributes,
+    ) -> String {
+        // Placement classes go on whichever element floats, the details wrapper when collapsible
+        let mut placement_classes = Vec::new();
+        if config.print_friendly {
+            placement_classes.push("infobox-print");
+        }
+        match config.align {
+            InfoboxAlign::Right => {},
+            InfoboxAlign::Left => placement_classes.push("infobox-align-left"),
+            InfoboxAlign::None => placement_classes.push("infobox-align-none"),
+        }
 
-## Name
-Testing
-"##;
+        let mut classes = vec![config.class.as_str()];
+        let mut collapsible_classes = vec!["infobox-collapsible"];
+        if config.collapsible {
+            collapsible_classes.extend(placement_classes);
+        } else {
+            classes.extend(placement_classes);
+        }
 
-        let expected_infobox = Infobox {
-            title: "Sunshine".into(),
-            sections: vec![
-                InfoboxSection::Image(InfoboxImage { title: Some("image".into()), url: "images/test.jpg".into() }),
-                InfoboxSection::Field(InfoboxField { name: "Name".into(), contents: "Testing".into() }),
-            ],
+        let thead = if config.sticky_header {
+            r##"<thead class="infobox-sticky-header">"##
+        } else {
+            "<thead>"
+        };
+
+        // Wide layouts split the title on `|`, one part per header cell
+        let mut title_parts: Vec<String> = if config.header_cells > 1 {
+            self.title_html.splitn(config.header_cells, '|').map(|part| part.trim().to_owned()).collect()
+        } else {
+            vec![self.title_html.clone()]
+        };
+        title_parts.resize(config.header_cells.max(1), String::new());
+        if config.title_anchor {
+            title_parts[0] = format!(r##"<a class="header" href="#{}">{}</a>"##, self.id_with_config(config), title_parts[0]);
+        }
+        if let Some(last_part) = title_parts.last_mut() {
+            last_part.push_str(&render_edit_link_html(config, chapter));
+        }
+
+        let header_cells = match title_parts.as_slice() {
+            [title] => format!(r##"<th colspan="2">{}</th>"##, title),
+            title_parts => title_parts.iter().map(|part| format!("<th>{}</th>", part)).collect(),
+        };
+
+        let mut attributes = vec![
+            format!(r##"class="{}""##, classes.join(" ")),
+            format!(r##"id="{}""##, self.id_with_config(config)),
+        ];
+        if let Some(language) = &config.language {
+            attributes.push(format!(r##"lang="{}""##, language));
+        }
+        if let Some(schema_type) = &config.schema_type {
+            attributes.push(format!(r##"itemscope itemtype="https://schema.org/{}""##, schema_type));
+        }
+        if !config.css_vars.is_empty() {
+            let css_vars: Vec<_> = config.css_vars.iter()
+                .map(|(name, value)| format!("--{}: {}", name.trim_start_matches('-'), value))
+                .collect();
+            attributes.push(format!(r##"style="{}""##, escape_html(&css_vars.join("; "), config.escape_style)));
+        }
+        for (name, value) in infobox_attributes {
+            if KNOWN_ATTRIBUTES.contains(&name.as_str()) {
+                continue;
+            }
+            attributes.push(match value {
+                Some(value) => format!(r##"{}{}="{}""##, config.attribute_prefix, name, escape_html(value, config.escape_style)),
+                None => format!("{}{}", config.attribute_prefix, name),
+            });
+        }
+
+        // One line per section, plus the divider rows and the table's own markup
+        let mut lines = Vec::with_capacity(2 * self.sections.len() + 9);
+        lines.extend(config.prefix_html.iter().cloned());
+        if config.collapsible {
+            lines.push(format!(r##"<details class="{}" open>"##, collapsible_classes.join(" ")));
+            lines.push(format!("<summary>{}</summary>", self.title_html));
+        }
+        lines.extend([
+            format!("<table {}>", attributes.join(" ")),
+            thead.into(),
+            "<tr>".into(),
+            header_cells,
+            "</tr>".into(),
+            "</thead>".into(),
+        ]);
+        lines.extend(self.render_rows_html(config, chapter));
+
+        let mut footer_rows = Vec::new();
+        if config.show_updated {
+            footer_rows.extend(render_updated_html(config, chapter));
+        }
+        if let (Some(nav), Some(chapter)) = (nav, chapter) {
+            footer_rows.push(render_nav_html(nav, chapter, config));
+        }
+        if let Some(footer_html) = &config.footer_html {
+            footer_rows.push(format!(r##"<tr class="infobox-footer">
+    <td colspan="2">{}</td>
+</tr>"##, footer_html));
+        }
+        if !footer_rows.is_empty() {
+            lines.push("<tfoot>".into());
+            lines.extend(footer_rows);
+            lines.push("</tfoot>".into());
+        }
+
+        lines.push("</table>".into());
+        if config.collapsible {
+            lines.push("</details>".into());
+        }
+        if config.emit_jsonld {
+            lines.push(self.render_jsonld_html(config));
+        }
+        lines.extend(config.suffix_html.iter().cloned());
+
+        lines.join("\n")
+    }
+
+    /// Renders the infobox with a user template, where `{id}`, `{title}` and `{rows}`
+    /// stand for the infobox's id, title and section rows.
+    fn render_template_html(&self, template: &str, config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+        lazy_static! {
+            static ref PLACEHOLDER_RE: Regex = Regex::new(r"\{(id|title|rows)\}").unwrap();
+        };
+
+        // One pass, so placeholders inside the substituted values are left alone
+        PLACEHOLDER_RE.replace_all(template, |capture: &Captures| match &capture[1] {
+            "id" => self.id_with_config(config),
+            "title" => self.title_html.clone(),
+            _ => self.render_rows_html(config, chapter).join("\n"),
+        })
+        .into_owned()
+    }
+
+    /// Renders the infobox as a two column GitHub flavored markdown table, with the title
+    /// as its header, for renderers to style as they would any other table.
+    ///
+    /// Titles and values are written as the same inline html the html layout uses, which
+    /// markdown passes through untouched, so formatting survives the round trip.
+    pub fn render_markdown_table(&self, config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+        let image = |image: &InfoboxImage| format!("![{}](<{}>)", image.alt.as_deref().unwrap_or_default(), image.url);
+
+        let mut rows = vec![[self.title_html.clone(), String::new()], ["---".into(), "---".into()]];
+        for section in &self.sections {
+            match section {
+                InfoboxSection::Image(img) => rows.push([image(img), String::new()]),
+                InfoboxSection::Gallery(gallery) => {
+                    let images: Vec<_> = gallery.images.iter().map(image).collect();
+                    rows.push([images.join(" "), String::new()]);
+                },
+                InfoboxSection::Field(field) => rows.push([
+                    escape_html(&field.name, config.escape_style),
+                    InfoboxSection::render_field_contents_html(field, config, chapter),
+                ]),
+                InfoboxSection::Description(description) if config.render_loose_content => {
+                    rows.push([description.contents.clone(), String::new()]);
+                },
+                InfoboxSection::Description(_) => {},
+            }
+        }
+
+        let cell = |text: &str| match markdown_table_cell(text) {
+            cell if cell.is_empty() => " ".to_owned(),
+            cell => format!(" {} ", cell),
+        };
+        rows.iter()
+            .map(|[name, value]| format!("|{}|{}|", cell(name), cell(value)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn render_rows_html(&self, config: &InfoboxConfig, chapter: Option<&Chapter>) -> Vec<String> {
+        let mut rows = Vec::with_capacity(2 * self.sections.len());
+
+        // Summaries go first, wherever they are in the source
+        let is_summary = |section: &InfoboxSection| {
+            std::matches!(section, InfoboxSection::Field(field) if config.summary_fields.contains(&field.name))
+        };
+        for section in self.sections.iter().filter(|section| is_summary(section)) {
+            if let InfoboxSection::Field(field) = section {
+                rows.push(InfoboxSection::render_summary_html(field, config));
+            }
+        }
+
+        for (i, section) in self.sections.iter().enumerate() {
+            match section {
+                section if is_summary(section) => continue,
+                InfoboxSection::Description(description) if i == 0 && config.lead_paragraph => {
+                    rows.push(InfoboxSection::render_lead_html(description));
+                },
+                InfoboxSection::Description(_) if !config.render_loose_content => continue,
+                section => rows.push(section.render_chapter_html(config, chapter)),
+            }
+
+            let next_section = self.sections.get(i + 1);
+            if config.divider_after_image
+                && std::matches!(section, InfoboxSection::Image(_) | InfoboxSection::Gallery(_))
+                && std::matches!(next_section, Some(InfoboxSection::Field(_))) {
+                rows.push(InfoboxSection::render_divider_html());
+            }
+        }
+
+        rows
+    }
+
+    /// Renders the infobox as a JSON-LD script, using field names as properties.
+    fn render_jsonld_html(&self, config: &InfoboxConfig) -> String {
+        let mut data = serde_json::Map::new();
+        data.insert("@context".into(), "https://schema.org".into());
+        data.insert("@type".into(), config.schema_type.as_deref().unwrap_or("Thing").into());
+        data.insert("name".into(), html_to_text(&self.title_html).into());
+        for section in &self.sections {
+            if let InfoboxSection::Field(field) = section {
+                data.insert(field.name.clone(), html_to_text(field.contents.trim()).into());
+            }
+        }
+
+        // Keep field contents from closing the script early
+        let json = serde_json::Value::Object(data).to_string().replace("</", "<\\/");
+        format!(r##"<script type="application/ld+json">{}</script>"##, json)
+    }
+}
+
+/// Prefix for links from `chapter` to book paths. Chapter paths are relative to the book
+/// root, while links are relative to the chapter they are in.
+fn chapter_path_to_root(chapter: Option<&Chapter>) -> String {
+    chapter.and_then(|chapter| chapter.path.as_ref()).map(path_to_root).unwrap_or_default()
+}
+
+fn render_nav_html(nav: &ChapterNav, chapter: &Chapter, config: &InfoboxConfig) -> String {
+    let path_to_root = chapter_path_to_root(Some(chapter));
+    let link = |target: &Option<ChapterLink>, text: fn(String) -> String| match target {
+        Some(target) => format!(
+            r##"<a href="{}{}">{}</a>"##,
+            path_to_root,
+            target.path.to_string_lossy().replace('\\', "/"),
+            text(escape_html(&target.name, config.escape_style)),
+        ),
+        None => String::new(),
+    };
+
+    format!(r##"<tr class="infobox-nav">
+    <td>{}</td>
+    <td>{}</td>
+</tr>"##, link(&nav.prev, |name| format!("← {}", name)), link(&nav.next, |name| format!("{} →", name)))
+}
+
+fn render_updated_html(config: &InfoboxConfig, chapter: Option<&Chapter>) -> Option<String> {
+    let chapter = chapter?;
+    let updated = match front_matter_date(&chapter.content) {
+        Some(date) => date.to_owned(),
+        None => {
+            let source_path = config.src_dir.join(chapter.source_path.as_ref()?);
+            let modified = fs::metadata(source_path).and_then(|metadata| metadata.modified()).ok()?;
+            DateTime::<Utc>::from(modified).format("%Y-%m-%d").to_string()
+        },
+    };
+
+    Some(format!(r##"<tr class="infobox-updated">
+    <td colspan="2">Last updated <time datetime="{0}">{0}</time></td>
+</tr>"##, escape_html(&updated, config.escape_style)))
+}
+
+/// The `date` of a chapter's `---` delimited front matter, if it has one.
+fn front_matter_date(content: &str) -> Option<&str> {
+    let mut lines = content.trim_start().lines();
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+
+    lines.take_while(|line| line.trim_end() != "---")
+        .find_map(|line| line.strip_prefix("date:"))
+        .map(|date| date.trim().trim_matches(|ch| ch == '"' || ch == '\''))
+}
+
+fn render_edit_link_html(config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+    let source_path = chapter.and_then(|chapter| chapter.source_path.as_ref());
+
+    match (&config.edit_url_template, source_path) {
+        (Some(template), Some(source_path)) => {
+            let path = source_path.to_string_lossy().replace('\\', "/");
+            let href = template.replace("{path}", &path);
+            format!(r##" <a class="infobox-edit" href="{}">edit</a>"##, escape_html(&href, config.escape_style))
+        },
+        _ => String::new(),
+    }
+}
+
+/// Renders an inline markdown event, or `None` for anything that isn't inline text
+/// or formatting.
+fn render_inline_html(event: &Event, config: &InfoboxConfig) -> Option<String> {
+    let html = match event {
+        Event::Text(text) => escape_html(text, config.escape_style),
+        Event::Code(code) => format!("<code>{}</code>", escape_html(code, config.escape_style)),
+        // Links are kept as-is so mdbook's link fixing (`.md` to `.html`,
+        // relative to the chapter path) applies to the rendered html
+        Event::Start(Tag::Link(_, url, title)) if !title.is_empty() => format!(
+            r##"<a href="{}" title="{}">"##,
+            escape_html(url, config.escape_style),
+            escape_html(title, config.escape_style),
+        ),
+        Event::Start(Tag::Link(_, url, _)) => format!(r##"<a href="{}">"##, escape_html(url, config.escape_style)),
+        Event::End(Tag::Link(..)) => "</a>".into(),
+        Event::Start(Tag::Strong) => "<strong>".into(),
+        Event::End(Tag::Strong) => "</strong>".into(),
+        Event::Start(Tag::Emphasis) => "<em>".into(),
+        Event::End(Tag::Emphasis) => "</em>".into(),
+        Event::Start(Tag::Strikethrough) => "<del>".into(),
+        Event::End(Tag::Strikethrough) => "</del>".into(),
+        // Other inline html is dropped, but explicit line breaks are kept
+        Event::Html(html) if is_line_break_tag(html) => "<br>".into(),
+        _ => return None,
+    };
+
+    Some(html)
+}
+
+fn is_line_break_tag(html: &str) -> bool {
+    std::matches!(html.trim().to_lowercase().as_str(), "<br>" | "<br/>" | "<br />")
+}
+
+/// Renders a field body made only of `**Label:** value` lines as a nested definition list.
+fn render_definition_list(events: &[Event], config: &InfoboxConfig) -> Option<String> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let mut line_start = true;
+    let mut in_label = false;
+
+    for event in events {
+        match (event, config.field_order) {
+            (Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph) | Event::SoftBreak | Event::HardBreak, _) => {
+                line_start = true;
+            },
+            (Event::Start(Tag::Strong), FieldOrder::NameValue) if line_start => {
+                entries.push((String::new(), String::new()));
+                line_start = false;
+                in_label = true;
+            },
+            (Event::Text(text), FieldOrder::ValueName) if line_start => {
+                entries.push((String::new(), text.to_string()));
+                line_start = false;
+            },
+            (Event::Start(Tag::Strong), FieldOrder::ValueName) if !line_start && entries.last()?.0.is_empty() => {
+                in_label = true;
+            },
+            (Event::End(Tag::Strong), _) if in_label => in_label = false,
+            (Event::Text(text), _) if !line_start => {
+                let (label, value) = entries.last_mut()?;
+                if in_label {
+                    label.push_str(text);
+                } else if config.field_order == FieldOrder::ValueName && !label.is_empty() {
+                    // The label has to end the line
+                    return None;
+                } else {
+                    value.push_str(text);
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    // A single bold line, or bold text that isn't a `Label:`, is just formatting
+    const SEPARATORS: [char; 4] = ['—', '–', '-', ':'];
+    let is_entry = |(label, value): &(String, String)| match config.field_order {
+        FieldOrder::NameValue => label.trim().ends_with(':'),
+        FieldOrder::ValueName => !label.trim().is_empty() && value.trim().ends_with(SEPARATORS),
+    };
+    if entries.len() < 2 || !entries.iter().all(is_entry) {
+        return None;
+    }
+
+    let mut lines = vec!["<dl>".to_string()];
+    for (label, value) in entries {
+        let label = escape_html(label.trim().trim_end_matches(':'), config.escape_style);
+        let value = match config.field_order {
+            FieldOrder::NameValue => value.trim(),
+            FieldOrder::ValueName => value.trim().trim_end_matches(SEPARATORS).trim_end(),
+        };
+        lines.push(format!("<dt>{}</dt><dd>{}</dd>", label, escape_html(value, config.escape_style)));
+    }
+    lines.push("</dl>".into());
+
+    Some(lines.join(""))
+}
+
+/// Renders a comma or line separated list of tags as tag chips. Only commas in the
+/// text between elements separate tags, never those in markup or inside a link.
+fn render_tags_html(value: &str) -> String {
+    lazy_static! {
+        static ref TOKEN_RE: Regex = Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9]*)[^>]*>|[,\n]").unwrap();
+    };
+
+    let mut tags = vec![String::new()];
+    let mut depth = 0usize;
+    let mut last = 0;
+    for capture in TOKEN_RE.captures_iter(value) {
+        let token = capture.get(0).unwrap();
+        *tags.last_mut().unwrap() += &value[last..token.start()];
+        last = token.end();
+
+        let separates = match capture.get(2).map(|name| name.as_str().to_ascii_lowercase()) {
+            Some(name) if std::matches!(name.as_str(), "br" | "li" | "ul" | "ol") => true,
+            Some(_) if token.as_str().ends_with("/>") => false,
+            Some(_) if &capture[1] == "/" => {
+                depth = depth.saturating_sub(1);
+                false
+            },
+            Some(_) => {
+                depth += 1;
+                false
+            },
+            None => depth == 0,
+        };
+
+        if separates {
+            tags.push(String::new());
+        } else {
+            *tags.last_mut().unwrap() += token.as_str();
+        }
+    }
+    *tags.last_mut().unwrap() += &value[last..];
+
+    tags.iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| format!(r##"<span class="infobox-tag">{}</span>"##, tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Cuts values longer than `max_length` characters, keeping the full text as a tooltip.
+fn render_truncated_html(value: &str, max_length: usize, config: &InfoboxConfig) -> Option<String> {
+    // Count and cut the decoded text so entities are neither split nor counted as several characters
+    let text = html_to_text(value.trim());
+    if text.chars().count() <= max_length {
+        return None;
+    }
+
+    let truncated: String = text.chars().take(max_length).collect();
+    Some(format!(
+        r##"<span title="{}">{}…</span>"##,
+        escape_html(&text, config.escape_style),
+        escape_html(truncated.trim_end(), config.escape_style),
+    ))
+}
+
+/// Renders percentages such as `75%` as a progress bar. Values outside of 0 to 100
+/// percent are left as written.
+fn render_progress_html(value: &str) -> Option<String> {
+    let value = value.trim();
+    let percentage: f64 = value.strip_suffix('%')?.trim().parse().ok()?;
+    if !(0.0..=100.0).contains(&percentage) {
+        return None;
+    }
+
+    Some(format!(r##"<progress value="{}" max="100">{}</progress>"##, percentage, value))
+}
+
+/// Renders ratings such as `4/5` as a row of filled and empty stars.
+/// Scales above `MAX_RATING` are left as written rather than rendered as huge rows.
+fn render_rating_html(value: &str) -> Option<String> {
+    const MAX_RATING: usize = 10;
+
+    let value = value.trim();
+    let (rating, out_of) = value.split_once('/')?;
+    let rating: usize = rating.trim().parse().ok()?;
+    let out_of: usize = out_of.trim().parse().ok()?;
+    if rating > out_of || out_of > MAX_RATING {
+        return None;
+    }
+
+    Some(format!(r##"<span class="infobox-rating" title="{}">{}{}</span>"##,
+        value, "★".repeat(rating), "☆".repeat(out_of - rating)))
+}
+
+/// Replaces the space between a number and the unit after it with `&nbsp;`, so
+/// values such as `23 years` never wrap between the two.
+fn render_nbsp_units_html(value: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"(\d) +([\p{L}%°])").unwrap();
+    };
+
+    replace_outside_code(value, |text| RE.replace_all(text, "$1&nbsp;$2").into_owned())
+}
+
+/// Rewrites the html outside of `<code>` and `<pre>` elements, leaving code samples as written.
+fn replace_outside_code(value: &str, mut replace: impl FnMut(&str) -> String) -> String {
+    lazy_static! {
+        static ref CODE_RE: Regex = Regex::new(r"(?s)<pre[\s>].*?</pre>|<code[\s>].*?</code>").unwrap();
+    };
+
+    let mut output = String::with_capacity(value.len());
+    let mut last = 0;
+    for code in CODE_RE.find_iter(value) {
+        output += &replace(&value[last..code.start()]);
+        output += code.as_str();
+        last = code.end();
+    }
+    output += &replace(&value[last..]);
+
+    output
+}
+
+fn render_color_swatch_html(value: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^#(?:[0-9a-fA-F]{3}|[0-9a-fA-F]{6})$").unwrap();
+    };
+
+    let value = value.trim();
+    if !RE.is_match(value) {
+        return None;
+    }
+
+    Some(format!(r##"<span class="infobox-swatch" style="background-color: {0}"></span> {0}"##, value))
+}
+
+/// Turns `[[Some Page]]` and `[[Some Page|label]]` into links to `some-page.md`, a file
+/// named after the page's slug at the root of the book's source directory.
+fn render_wiki_links_html(value: &str, chapter: Option<&Chapter>) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\[\[([^\]|]+)(?:\|([^\]]+))?\]\]").unwrap();
+    };
+
+    let path_to_root = chapter_path_to_root(chapter);
+    RE.replace_all(value, |capture: &Captures| {
+        let page = capture[1].trim();
+        let label = capture.get(2).map_or(page, |label| label.as_str().trim());
+        format!(r##"<a href="{}{}.md">{}</a>"##, path_to_root, normalize_id(page), label)
+    })
+    .into_owned()
+}
+
+/// Adds thousands separators to whole numbers of five or more digits, such as
+/// `1000000`, using the separator of `language`. Html tags and code are left untouched.
+fn format_numbers(value: &str, language: Option<&str>) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"<[^>]*>|\d+(?:[.,]\d+)*").unwrap();
+    };
+
+    let primary_language = language.and_then(|language| language.split(['-', '_']).next()).unwrap_or_default();
+    let separator = match primary_language {
+        "pt" | "de" | "es" | "it" | "nl" | "id" | "tr" => ".",
+        "fr" | "pl" | "ru" | "sv" | "fi" | "cs" | "nb" => "\u{202f}",
+        _ => ",",
+    };
+
+    replace_outside_code(value, |text| RE.replace_all(text, |capture: &Captures| {
+        let number = &capture[0];
+        if number.len() < 5 || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+            return number.to_owned();
+        }
+
+        let digits: Vec<_> = number.chars().collect();
+        let groups: Vec<String> = digits.rchunks(3)
+            .rev()
+            .map(|group| group.iter().collect())
+            .collect();
+        groups.join(separator)
+    })
+    .into_owned())
+}
+
+/// Turns `{漢字|かんじ}` into ruby annotated text.
+fn render_ruby_html(value: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\{([^{}|]+)\|([^{}|]+)\}").unwrap();
+    };
+
+    RE.replace_all(value, "<ruby>$1<rt>$2</rt></ruby>").into_owned()
+}
+
+fn render_boolean_icon_html(value: &str, config: &InfoboxConfig) -> Option<String> {
+    let value = value.trim();
+    let icon = match value.to_lowercase().as_str() {
+        "yes" | "true" => &config.true_icon,
+        "no" | "false" => &config.false_icon,
+        _ => return None,
+    };
+
+    Some(format!(r##"<span class="infobox-boolean" title="{}">{}</span>"##, value, icon))
+}
+
+/// Turns a field name such as `Birth date` into a schema.org property name (`birthDate`).
+fn schema_property_name(name: &str) -> String {
+    name.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .map(|(i, word)| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap_or_default();
+            let first = if i == 0 { first.to_lowercase().to_string() } else { first.to_uppercase().to_string() };
+
+            first + chars.as_str()
+        })
+        .collect()
+}
+
+fn is_diagram_language(lang: &str) -> bool {
+    lang == "mermaid" || lang == "plantuml"
+}
+
+/// Turns paragraphs made up of `Term` lines, each followed by a `: Definition` line,
+/// into a field heading and value per pair. pulldown-cmark has no description lists
+/// of its own.
+fn expand_description_lists(events: Vec<(Event<'_>, Range<usize>)>) -> Vec<(Event<'_>, Range<usize>)> {
+    let mut expanded = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+
+    while let Some((event, range)) = events.next() {
+        if event != Event::Start(Tag::Paragraph) {
+            expanded.push((event, range));
+            continue;
+        }
+
+        let mut paragraph = vec![(event, range)];
+        for (event, range) in events.by_ref() {
+            let is_end = event == Event::End(Tag::Paragraph);
+            paragraph.push((event, range));
+            if is_end {
+                break;
+            }
+        }
+
+        let lines: Vec<_> = paragraph[1..paragraph.len() - 1]
+            .split(|(event, _)| std::matches!(event, Event::SoftBreak | Event::HardBreak))
+            .collect();
+        let is_description_list = lines.len() % 2 == 0 && lines.chunks(2).all(|pair| {
+            let is_term = pair[0].iter().all(|(event, _)| std::matches!(event, Event::Text(_)));
+            let is_definition = std::matches!(pair[1].first(), Some((Event::Text(text), _)) if text.starts_with(':'));
+            is_term && is_definition && !pair[0].is_empty()
+        });
+        if !is_description_list {
+            expanded.extend(paragraph);
+            continue;
+        }
+
+        for pair in lines.chunks(2) {
+            let (term, definition) = (pair[0], pair[1]);
+            let heading = Tag::Heading(HeadingLevel::H6, None, Vec::new());
+            let term_range = term[0].1.clone();
+            let definition_range = definition[0].1.clone();
+
+            expanded.push((Event::Start(heading.clone()), term_range.clone()));
+            expanded.extend(term.iter().cloned());
+            expanded.push((Event::End(heading), term_range));
+
+            expanded.push((Event::Start(Tag::Paragraph), definition_range.clone()));
+            for (event, range) in definition.iter().cloned() {
+                match event {
+                    Event::Text(text) if range == definition_range => {
+                        let text = text.trim_start_matches(':').trim_start();
+                        if !text.is_empty() {
+                            expanded.push((Event::Text(text.to_owned().into()), range));
+                        }
+                    },
+                    event => expanded.push((event, range)),
+                }
+            }
+            expanded.push((Event::End(Tag::Paragraph), definition_range));
+        }
+    }
+
+    expanded
+}
+
+#[derive(Default)]
+struct FieldHints {
+    align: Option<String>,
+    dir: Option<String>,
+}
+
+/// Splits hints such as `{align=right dir=rtl}` off the end of a field name.
+/// Braces holding anything but known hints, as in `## Set {A}`, are part of the name.
+fn parse_field_hints(name: &str) -> (String, FieldHints) {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(.*?)\s*\{([^{}]*)\}\s*$").unwrap();
+    };
+
+    let unhinted = || (name.to_owned(), FieldHints::default());
+    let capture = match RE.captures(name) {
+        Some(capture) if !capture[2].trim().is_empty() => capture,
+        _ => return unhinted(),
+    };
+
+    let mut hints = FieldHints::default();
+    for hint in capture[2].split_whitespace() {
+        match hint.split_once('=') {
+            Some(("align", value @ ("left" | "center" | "right" | "justify"))) => hints.align = Some(value.to_owned()),
+            Some(("dir", value @ ("ltr" | "rtl" | "auto"))) => hints.dir = Some(value.to_owned()),
+            _ => return unhinted(),
+        }
+    }
+
+    (capture[1].to_owned(), hints)
+}
+
+/// Puts html on the single line a markdown table cell allows. Newlines inside `<pre>` are
+/// kept as `&#10;` so code blocks keep their lines, and pipes are escaped so they don't
+/// start a new cell.
+fn markdown_table_cell(html: &str) -> String {
+    lazy_static! {
+        static ref PRE_RE: Regex = Regex::new(r"(?s)<pre[\s>].*?</pre>").unwrap();
+    };
+
+    let mut cell = String::with_capacity(html.len());
+    let mut last = 0;
+    let html = html.trim();
+    for pre in PRE_RE.find_iter(html) {
+        cell += &html[last..pre.start()].replace('\n', " ");
+        cell += &pre.as_str().replace('\n', "&#10;");
+        last = pre.end();
+    }
+    cell += &html[last..].replace('\n', " ");
+
+    cell.replace('|', "\\|")
+}
+
+/// Whether nothing but images follows, up to the next field or the end of the infobox.
+fn only_images_left_in_field(iter: &EventIter) -> bool {
+    let mut in_image = false;
+    for (event, _) in iter.clone() {
+        match event {
+            Event::Start(Tag::Heading(..)) => return true,
+            event if is_csv_block_start(&event) => return true,
+            Event::Start(Tag::Image(..)) => in_image = true,
+            Event::End(Tag::Image(..)) => in_image = false,
+            _ if in_image => {},
+            Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph) | Event::SoftBreak | Event::HardBreak => {},
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+fn is_csv_block_start(event: &Event) -> bool {
+    std::matches!(event, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if lang.as_ref() == "csv")
+}
+
+impl InfoboxSection {
+    /// Byte range of the section within the infobox contents.
+    pub fn range(&self) -> &Range<usize> {
+        match &self {
+            Self::Field(field) => &field.range,
+            Self::Image(image) => &image.range,
+            Self::Gallery(gallery) => &gallery.range,
+            Self::Description(description) => &description.range,
+        }
+    }
+
+    pub fn render_html(&self, config: &InfoboxConfig) -> String {
+        self.render_chapter_html(config, None)
+    }
+
+    fn render_chapter_html(&self, config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+        match &self {
+            Self::Field(field) => Self::render_field_html(field, config, chapter),
+            Self::Image(image) => Self::render_image_html(image, config),
+            Self::Gallery(gallery) => Self::render_gallery_html(gallery, config),
+            Self::Description(description) => Self::render_description_html(description),
+        }
+    }
+
+    fn render_lead_html(description: &InfoboxDescription) -> String {
+        format!(r##"
+<tr class="infobox-lead">
+    <td colspan="2">{}</td>
+</tr>"##, description.contents)
+    }
+
+    fn render_summary_html(field: &InfoboxField, config: &InfoboxConfig) -> String {
+        format!(r##"
+<tr class="infobox-summary">
+    <td colspan="2">{}</td>
+</tr>"##, Self::render_field_value_html(field, config))
+    }
+
+    fn render_description_html(description: &InfoboxDescription) -> String {
+        format!(r##"
+<tr class="infobox-description">
+    <td colspan="2">{}</td>
+</tr>"##, description.contents)
+    }
+
+    fn render_field_html(field: &InfoboxField, config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+        let mut contents = Self::render_field_contents_html(field, config, chapter);
+        if config.collapsible_fields && html_to_text(&field.contents).chars().count() > config.collapsible_field_length {
+            contents = format!("<details><summary>Show more</summary>{}</details>", contents);
+        }
+
+        let mut value_attributes = match &config.schema_type {
+            Some(_) => format!(r##" itemprop="{}""##, schema_property_name(&field.name)),
+            None => String::new(),
+        };
+        if let Some(dir) = &field.dir {
+            value_attributes += &format!(r##" dir="{}""##, dir);
+        }
+        if let Some(align) = &field.align {
+            value_attributes += &format!(r##" style="text-align:{}""##, align);
+        }
+
+        format!(r##"
+<tr>
+    <td>{}</td>
+    <td{}>{}</td>
+</tr>"##, escape_html(&field.name, config.escape_style), value_attributes, contents)
+    }
+
+    /// The field value with the text transforms every layout applies on top of it.
+    fn render_field_contents_html(field: &InfoboxField, config: &InfoboxConfig, chapter: Option<&Chapter>) -> String {
+        let mut contents = Self::render_field_value_html(field, config);
+        if config.wiki_links {
+            contents = render_wiki_links_html(&contents, chapter);
+        }
+        if config.ruby {
+            contents = render_ruby_html(&contents);
+        }
+        if config.format_numbers {
+            contents = format_numbers(&contents, config.language.as_deref());
+        }
+        if let Some(line_break_marker) = &config.line_break_marker {
+            contents = contents.replace(line_break_marker.as_str(), "<br>");
+        }
+
+        contents
+    }
+
+    fn render_field_value_html(field: &InfoboxField, config: &InfoboxConfig) -> String {
+        if config.address_fields.contains(&field.name) {
+            return format!("<address>{}</address>", field.contents.trim().replace('\n', "<br>"));
+        }
+
+        if config.boolean_icons {
+            if let Some(boolean_icon) = render_boolean_icon_html(&field.contents, config) {
+                return boolean_icon;
+            }
+        }
+
+        if config.color_swatch {
+            if let Some(swatch) = render_color_swatch_html(&field.contents) {
+                return swatch;
+            }
+        }
+
+        if config.progress_fields.contains(&field.name) {
+            if let Some(progress) = render_progress_html(&field.contents) {
+                return progress;
+            }
+        }
+
+        if config.rating_fields.contains(&field.name) {
+            if let Some(rating) = render_rating_html(&field.contents) {
+                return rating;
+            }
+        }
+
+        if config.tag_fields.contains(&field.name) {
+            return render_tags_html(&field.contents);
+        }
+
+        if config.kbd_fields.contains(&field.name) {
+            return field.contents.replace("<code>", "<kbd>").replace("</code>", "</kbd>");
+        }
+
+        if let Some(max_field_length) = config.max_field_length {
+            if let Some(truncated) = render_truncated_html(&field.contents, max_field_length, config) {
+                return truncated;
+            }
+        }
+
+        if config.nbsp_units {
+            return render_nbsp_units_html(&field.contents);
+        }
+
+        field.contents.clone()
+    }
+
+    fn render_divider_html() -> String {
+        r##"
+<tr class="infobox-divider">
+    <td colspan="2"><hr/></td>
+</tr>"##.into()
+    }
+
+    fn render_image_html(image: &InfoboxImage, config: &InfoboxConfig) -> String {
+        format!(r##"
+<tr>
+    <td colspan="2">{}</td>
+</tr>"##, Self::render_image_cell_html(image, config))
+    }
+
+    fn render_gallery_html(gallery: &InfoboxGallery, config: &InfoboxConfig) -> String {
+        let images: String = gallery.images.iter()
+            .map(|image| Self::render_image_cell_html(image, config))
+            .collect();
+
+        format!(r##"
+<tr class="infobox-gallery">
+    <td colspan="2">{}</td>
+</tr>"##, images)
+    }
+
+    fn render_image_cell_html(image: &InfoboxImage, config: &InfoboxConfig) -> String {
+        let title = image.title.clone().or_else(|| match config.image_title_fallback {
+            ImageTitleFallback::Alt => image.alt.clone(),
+            ImageTitleFallback::None => None,
+        });
+
+        let escape = |value: &str| escape_html(value, config.escape_style);
+        let mut attributes = vec![format!(r##"src="{}""##, escape(&image.url))];
+        if let Some(alt) = &image.alt {
+            attributes.push(format!(r##"alt="{}""##, escape(alt)));
+        }
+        if let Some(title) = &title {
+            attributes.push(format!(r##"title="{}""##, escape(title)));
+        }
+        if let Some(srcset) = &image.srcset {
+            attributes.push(format!(r##"srcset="{}""##, escape(srcset)));
+        }
+        if let Some(width) = image.width {
+            attributes.push(format!(r##"width="{}""##, width));
+        }
+        if let Some(height) = image.height {
+            attributes.push(format!(r##"height="{}""##, height));
+        }
+        if config.zoomable_images {
+            attributes.push(config.zoom_attribute.clone());
+        }
+        let alt_fallback = image.alt.as_ref().filter(|_| config.show_alt_fallback);
+        if alt_fallback.is_some() {
+            attributes.push(r##"onerror="this.hidden=true;this.nextElementSibling.hidden=false""##.into());
+        }
+
+        let mut styles = Vec::new();
+        if let Some(aspect_ratio) = &image.aspect_ratio {
+            styles.push(format!("aspect-ratio: {}", aspect_ratio));
+        }
+        if let Some(border) = &config.image_border {
+            styles.push(format!("border: {}", border));
+        }
+        if let Some(shadow) = &config.image_shadow {
+            styles.push(format!("box-shadow: {}", shadow));
+        }
+        if !styles.is_empty() {
+            attributes.push(format!(r##"style="{}""##, styles.join("; ")));
+        }
+
+        let mut img = format!("<img {}/>", attributes.join(" "));
+        if let Some(alt) = alt_fallback {
+            img += &format!(r##"<span class="infobox-image-fallback" hidden>{}</span>"##, escape(alt));
+        }
+        if let Some(full) = &image.full {
+            let target = if config.open_in_new_tab { r##" target="_blank" rel="noopener""## } else { "" };
+            img = format!(r##"<a href="{}"{}>{}</a>"##, escape(full), target, img);
+        }
+        let caption = image.alt.as_ref().or(image.title.as_ref());
+        let framed = config.framed_images && caption.is_some();
+        let default_semantics = if framed { ImageSemantics::Figure } else { config.image_semantics };
+        match image.semantics.unwrap_or(default_semantics) {
+            ImageSemantics::Img => img,
+            ImageSemantics::Figure => {
+                let figcaption = match caption {
+                    Some(caption) => format!("<figcaption>{}</figcaption>", escape(caption)),
+                    None => String::new(),
+                };
+                let class = if framed { r##" class="infobox-frame""## } else { "" };
+
+                format!("<figure{}>{}{}</figure>", class, img, figcaption)
+            },
+        }
+    }
+}
+
+impl InfoboxImage {
+    fn set_option(&mut self, option: &str) -> MdbookResult<()> {
+        let (key, value) = option.split_once('=')
+            .ok_or(anyhow!("invalid image option: {:?}", option))?;
+
+        match key.trim() {
+            "srcset" => self.srcset = Some(value.trim().into()),
+            "aspect-ratio" => self.aspect_ratio = Some(parse_aspect_ratio(value)?),
+            "full" => self.full = Some(value.trim().into()),
+            "width" => self.width = Some(parse_image_size(value)?),
+            "height" => self.height = Some(parse_image_size(value)?),
+            "semantics" => self.semantics = Some(match value.trim() {
+                "img" => ImageSemantics::Img,
+                "figure" => ImageSemantics::Figure,
+                value => return Err(anyhow!("unknown image semantics: {:?}", value)),
+            }),
+            key => return Err(anyhow!("unknown image option: {:?}", key)),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_image_size(value: &str) -> MdbookResult<u32> {
+    value.trim().trim_end_matches("px").parse()
+        .map_err(|_| anyhow!("invalid image size: {:?}", value))
+}
+
+/// Checks that an aspect ratio is a `width/height` pair of numbers, such as `4/3`.
+fn parse_aspect_ratio(value: &str) -> MdbookResult<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^\d+(\.\d+)?\s*/\s*\d+(\.\d+)?$").unwrap();
+    };
+
+    let value = value.trim();
+    if !RE.is_match(value) {
+        return Err(anyhow!("invalid image aspect ratio: {:?}", value));
+    }
+
+    Ok(value.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::config::EscapeStyle;
+
+    #[test]
+    fn test_find_infoboxes_contents() {
+        let document = r##"
+# Sunshine
+{{#infobox}}
+# Infobox title
+## Infobox field
+Field contents
+{{/infobox}}
+
+# Description
+"##;
+        let expected_infobox_contents = r##"
+# Infobox title
+## Infobox field
+Field contents
+"##;
+
+        let infoboxes_contents = find_infoboxes_contents(document, &infobox_regex(DEFAULT_TAG));
+        assert_eq!(1, infoboxes_contents.len());
+
+        let infobox_contents = &infoboxes_contents[0].contents;
+
+        assert_eq!(expected_infobox_contents, infobox_contents);
+    }
+
+    #[test]
+    fn test_find_adjacent_infoboxes_contents() {
+        let document = "{{#infobox}}\n# Sunshine\n{{/infobox}}\n{{#infobox}}\n# Luna\n{{/infobox}}\n";
+
+        let infoboxes_contents = find_infoboxes_contents(document, &infobox_regex(DEFAULT_TAG));
+        assert_eq!(2, infoboxes_contents.len());
+
+        assert_eq!("\n# Sunshine\n", infoboxes_contents[0].contents);
+        assert_eq!(0..36, infoboxes_contents[0].range);
+        assert_eq!("\n# Luna\n", infoboxes_contents[1].contents);
+        assert_eq!(37..69, infoboxes_contents[1].range);
+    }
+
+    #[test]
+    fn test_infobox_ranges() {
+        let document = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+
+{{#infobox}}
+# Luna
+{{/infobox}}
+"##;
+
+        let ranges: Vec<_> = infobox_ranges(document)
+            .into_iter()
+            .map(|range| &document[range])
+            .collect();
+
+        assert_eq!(vec!["{{#infobox}}\n# Sunshine\n{{/infobox}}", "{{#infobox}}\n# Luna\n{{/infobox}}"], ranges);
+    }
+
+    #[test]
+    fn test_from_markdown_contents_simple() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+
+## Birthday
+1999-07-27
+
+## Age
+23 years
+"##;
+
+        let expected_infobox = Infobox {
+            title: "Sunshine".into(),
+            title_html: "Sunshine".into(),
+            sections: vec![
+                InfoboxSection::Field(InfoboxField { name: "Name".into(), contents: "Testing".into(), align: None, dir: None, range: 12..28 }),
+                InfoboxSection::Field(InfoboxField { name: "Birthday".into(), contents: "1999-07-27".into(), align: None, dir: None, range: 29..52 }),
+                InfoboxSection::Field(InfoboxField { name: "Age".into(), contents: "23 years".into(), align: None, dir: None, range: 53..69 }),
+            ],
         };
 
         assert_eq!(expected_infobox, Infobox::from_markdown_content(infobox_contents).unwrap());
     }
 
     #[test]
-    fn test_preprocessor() {
+    fn test_from_markdown_contents_with_image() {
+        let infobox_contents = r##"
+# Sunshine
+![image](images/test.jpg)
+
+## Name
+Testing
+"##;
+
+        let expected_infobox = Infobox {
+            title: "Sunshine".into(),
+            title_html: "Sunshine".into(),
+            sections: vec![
+                InfoboxSection::Image(InfoboxImage {
+                    alt: Some("image".into()),
+                    title: None,
+                    url: "images/test.jpg".into(),
+                    srcset: None,
+                    aspect_ratio: None,
+                    full: None,
+                    width: None,
+                    height: None,
+                    semantics: None,
+                    range: 12..37,
+                }),
+                InfoboxSection::Field(InfoboxField { name: "Name".into(), contents: "Testing".into(), align: None, dir: None, range: 39..55 }),
+            ],
+        };
+
+        assert_eq!(expected_infobox, Infobox::from_markdown_content(infobox_contents).unwrap());
+    }
+
+    #[test]
+    fn test_section_ranges() {
+        let infobox_contents = r##"
+# Sunshine
+![image](images/test.jpg)
+
+## Name
+Testing
+
+## Age
+23 years
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let sections: Vec<_> = infobox.sections.iter()
+            .map(|section| &infobox_contents[section.range().clone()])
+            .collect();
+
+        assert_eq!(vec!["![image](images/test.jpg)", "## Name\nTesting\n", "## Age\n23 years\n"], sections);
+    }
+
+    #[test]
+    fn test_image_srcset() {
+        let infobox_contents = r##"
+# Sunshine
+![image|srcset=images/test-2x.jpg 2x](images/test.jpg)
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let expected_image = InfoboxImage {
+            alt: Some("image".into()),
+            title: None,
+            url: "images/test.jpg".into(),
+            srcset: Some("images/test-2x.jpg 2x".into()),
+            aspect_ratio: None,
+            full: None,
+            width: None,
+            height: None,
+            semantics: None,
+            range: 12..66,
+        };
+
+        assert_eq!(vec![InfoboxSection::Image(expected_image)], infobox.sections);
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<img src="images/test.jpg" alt="image" title="image" srcset="images/test-2x.jpg 2x"/>"##));
+    }
+
+    #[test]
+    fn test_field_chapter_link() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Mother
+[Plum Priestess](plum-priestess.md)
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&book).contains(r##"<td><a href="plum-priestess.md">Plum Priestess</a></td>"##));
+    }
+
+    #[test]
+    fn test_field_relative_link() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Home world
+Born on [Krypton](../places/krypton.md#history "The home world"), raised on Earth
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&book).contains(
+            r##"<td>Born on <a href="../places/krypton.md#history" title="The home world">Krypton</a>, raised on Earth</td>"##
+        ));
+    }
+
+    #[test]
+    fn test_divider_after_image() {
+        let infobox_contents = r##"
+# Sunshine
+![image](images/test.jpg)
+
+## Name
+Testing
+"##;
+
+        let config = InfoboxConfig { divider_after_image: true, ..Default::default() };
+        let html = Infobox::from_markdown_content(infobox_contents).unwrap().render_html(&config);
+
+        let image = html.find("<img").unwrap();
+        let divider = html.find(r##"<tr class="infobox-divider">"##).unwrap();
+        let field = html.find("<td>Name</td>").unwrap();
+        assert!(image < divider && divider < field);
+        assert!(!Infobox::from_markdown_content(infobox_contents).unwrap()
+            .render_html(&InfoboxConfig::default())
+            .contains("infobox-divider"));
+    }
+
+    #[test]
+    fn test_sticky_header() {
+        let infobox = Infobox::from_markdown_content("# Sunshine").unwrap();
+        let config = InfoboxConfig { sticky_header: true, ..Default::default() };
+
+        assert!(infobox.render_html(&config).contains(r##"<thead class="infobox-sticky-header">"##));
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains("<thead>"));
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "required-fields": ["Name", "Age"] }));
+        let error = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap_err();
+        assert!(error.to_string().contains("Chapter 1"));
+        assert!(format!("{:#}", error).contains(r##"missing required field "Age""##));
+
+        let ctx = mock_context_with_config("html", json!({ "required-fields": ["Name"] }));
+        assert!(InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).is_ok());
+    }
+
+    #[test]
+    fn test_image_title_fallback() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg)").unwrap();
+
+        let config = InfoboxConfig { image_title_fallback: ImageTitleFallback::Alt, ..Default::default() };
+        assert!(infobox.render_html(&config).contains(r##"<img src="images/test.jpg" alt="image" title="image"/>"##));
+
+        let config = InfoboxConfig { image_title_fallback: ImageTitleFallback::None, ..Default::default() };
+        assert!(infobox.render_html(&config).contains(r##"<img src="images/test.jpg" alt="image"/>"##));
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg \"Portrait\")").unwrap();
+        assert!(infobox.render_html(&config).contains(r##"<img src="images/test.jpg" alt="image" title="Portrait"/>"##));
+    }
+
+    #[test]
+    fn test_csv_fields() {
+        let infobox_contents = r##"
+# Sunshine
+```csv
+Name,Testing
+Age,23 years
+```
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let fields: Vec<_> = infobox.sections.iter()
+            .map(|section| match section {
+                InfoboxSection::Field(field) => (field.name.as_str(), field.contents.as_str()),
+                section => panic!("unexpected section: {:?}", section),
+            })
+            .collect();
+        assert_eq!(vec![("Name", "Testing"), ("Age", "23 years")], fields);
+
+        let html = infobox.render_html(&InfoboxConfig::default());
+        assert!(html.contains("<td>Name</td>\n    <td>Testing</td>"));
+        assert!(html.contains("<td>Age</td>\n    <td>23 years</td>"));
+    }
+
+    #[test]
+    fn test_trim_surrounding_blanks() {
+        let chapter_contents = r##"# Sunshine
+
+
+
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+# History
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "trim-surrounding-blanks": true }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let content = chapter_content(&book);
+
+        assert!(content.starts_with("# Sunshine\n\n<table"));
+        assert!(content.ends_with("</table>\n\n# History\n"));
+    }
+
+    #[test]
+    fn test_definition_list_field() {
+        let infobox_contents = r##"
+# Sunshine
+## Stats
+**Strength:** 10
+**Speed:** 7
+
+## Age
+23 years
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td><dl><dt>Strength</dt><dd>10</dd><dt>Speed</dt><dd>7</dd></dl></td>"));
+        assert!(html.contains("<td>23 years</td>"));
+    }
+
+    #[test]
+    fn test_definition_list_field_value_name_order() {
+        let infobox_contents = r##"
+# Sunshine
+## Stats
+10 — **Strength**
+7 — **Speed**
+"##;
+
+        let config = InfoboxConfig { field_order: FieldOrder::ValueName, ..Default::default() };
+        let infobox = Infobox::from_markdown_content_with_config(infobox_contents, &config).unwrap();
+
+        assert!(infobox.render_html(&config)
+            .contains("<td><dl><dt>Strength</dt><dd>10</dd><dt>Speed</dt><dd>7</dd></dl></td>"));
+    }
+
+    #[test]
+    fn test_unsupported_renderer() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("epub");
+        let book = mock_book(chapter_contents);
+        let expected_book = book.clone();
+
+        assert!(!InfoboxPreprocessor.supports_renderer("epub"));
+        assert_eq!(expected_book, InfoboxPreprocessor.run(&ctx, book).unwrap());
+    }
+
+    #[test]
+    fn test_markdown_renderer_default_layout() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("markdown");
+        let book = mock_book(chapter_contents);
+        let expected_book = book.clone();
+
+        assert!(InfoboxPreprocessor.supports_renderer("markdown"));
+        assert_eq!(expected_book, InfoboxPreprocessor.run(&ctx, book).unwrap());
+    }
+
+    #[test]
+    fn test_render_loose_content() {
+        let infobox_contents = r##"
+# Sunshine
+A child of the [Plum Priestess](plum-priestess.md).
+
+## Name
+Testing
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let expected_row = r##"<tr class="infobox-description">
+    <td colspan="2">A child of the <a href="plum-priestess.md">Plum Priestess</a>.</td>
+</tr>"##;
+
+        let config = InfoboxConfig { render_loose_content: true, ..Default::default() };
+        let html = infobox.render_html(&config);
+        assert!(html.contains(expected_row));
+        assert!(html.find(expected_row).unwrap() < html.find("<td>Name</td>").unwrap());
+
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("infobox-description"));
+    }
+
+    #[test]
+    fn test_counts() {
+        let infobox_contents = r##"
+# Sunshine
+![image](images/test.jpg)
+A description.
+
+## Name
+Testing
+
+## Age
+23 years
+"##;
+
+        assert_eq!((1, 2), Infobox::from_markdown_content(infobox_contents).unwrap().counts());
+    }
+
+    #[test]
+    fn test_image_semantics_figure() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![Sunshine as a child](images/test.jpg)").unwrap();
+        let config = InfoboxConfig { image_semantics: ImageSemantics::Figure, ..Default::default() };
+
+        let expected_cell = concat!(
+            r##"<td colspan="2"><figure><img src="images/test.jpg" alt="Sunshine as a child" title="Sunshine as a child"/>"##,
+            r##"<figcaption>Sunshine as a child</figcaption></figure></td>"##,
+        );
+        assert!(infobox.render_html(&config).contains(expected_cell));
+    }
+
+    #[test]
+    fn test_prefix_suffix_html() {
+        let infobox = Infobox::from_markdown_content("# Sunshine").unwrap();
+        let config = InfoboxConfig {
+            prefix_html: Some(r##"<div class="infobox-wrapper">"##.into()),
+            suffix_html: Some("</div>".into()),
+            ..Default::default()
+        };
+
+        let html = infobox.render_html(&config);
+        assert!(html.starts_with("<div class=\"infobox-wrapper\">\n<table"));
+        assert!(html.ends_with("</table>\n</div>"));
+    }
+
+    #[test]
+    fn test_address_fields() {
+        let infobox_contents = r##"
+# Plum Principality
+## Address
+Royal Palace
+Plum Principality
+
+## Location
+Coast
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+        assert!(html.contains("<td><address>Royal Palace<br>Plum Principality</address></td>"));
+
+        let config = InfoboxConfig { address_fields: vec!["Location".into()], ..Default::default() };
+        let html = infobox.render_html(&config);
+        assert!(html.contains("<td><address>Coast</address></td>"));
+        assert!(!html.contains("<address>Royal Palace"));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let infobox_contents = r##"
+# Sunshine
+## Stats
+| Strength | Speed |
+|----------|-------|
+| 10       | 7     |
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let InfoboxSection::Field(field) = &infobox.sections[0] else { panic!() };
+        assert!(!field.contents.contains('|'));
+
+        let config: InfoboxConfig = serde_json::from_value(json!({ "extensions": ["footnotes"] })).unwrap();
+        let infobox = Infobox::from_markdown_content_with_options(infobox_contents, config.parser_options()).unwrap();
+        let InfoboxSection::Field(field) = &infobox.sections[0] else { panic!() };
+        assert!(field.contents.starts_with("| Strength | Speed |"));
+    }
+
+    #[test]
+    fn test_edit_url_template() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({
+            "edit-url-template": "https://github.com/starillume/wikiplum/edit/main/src/{path}"
+        }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        let expected_link = r##"<a class="infobox-edit" href="https://github.com/starillume/wikiplum/edit/main/src/chapter_1.md">edit</a>"##;
+        assert!(chapter_content(&book).contains(&format!(r##"<th colspan="2">Sunshine {}</th>"##, expected_link)));
+    }
+
+    #[test]
+    fn test_duplicate_infobox_ids() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("html");
+        let book = mock_book_with_chapters(&[chapter_contents, chapter_contents]);
+        assert!(InfoboxPreprocessor.run(&ctx, book).is_ok());
+
+        let ctx = mock_context_with_config("html", json!({ "deny-duplicate-ids": true }));
+        let book = mock_book_with_chapters(&[chapter_contents, chapter_contents]);
+        let error = InfoboxPreprocessor.run(&ctx, book).unwrap_err();
+        assert_eq!(r##"duplicate infobox ids: "infobox-sunshine" (Chapter 1, Chapter 2)"##, error.to_string());
+
+        let book = mock_book_with_chapters(&[chapter_contents, &chapter_contents.replace("Sunshine", "Luna")]);
+        assert!(InfoboxPreprocessor.run(&ctx, book).is_ok());
+    }
+
+    #[test]
+    fn test_collapsible_fields() {
+        let long_value = vec!["Sunshine"; 30].join(" ");
+        let infobox_contents = format!("# Sunshine\n## Name\nTesting\n## Nickname\n{}", long_value);
+        let infobox = Infobox::from_markdown_content(&infobox_contents).unwrap();
+
+        let config = InfoboxConfig { collapsible_fields: true, ..Default::default() };
+        let html = infobox.render_html(&config);
+        assert!(html.contains(&format!("<td><details><summary>Show more</summary>{}</details></td>", long_value)));
+        assert!(html.contains("<td>Testing</td>"));
+
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("<details>"));
+
+        // Only the text counts towards the length, not the markup around it
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Home\n[Plum](https://example.com/a/very/long/url/to/the/plum/house.html)").unwrap();
+        let config = InfoboxConfig { collapsible_fields: true, collapsible_field_length: 20, ..Default::default() };
+        assert!(!infobox.render_html(&config).contains("<details>"));
+    }
+
+    #[test]
+    fn test_retain_and_map_fields() {
+        let infobox_contents = r##"
+# Sunshine
+![image](images/test.jpg)
+
+## Name
+Testing
+
+## Age
+23 years
+"##;
+
+        let mut infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        infobox.retain_fields(|field| field.name != "Age");
+        infobox.map_fields(|field| InfoboxField { name: field.name.to_uppercase(), ..field });
+
+        let html = infobox.render_html(&InfoboxConfig::default());
+        assert_eq!((1, 1), infobox.counts());
+        assert!(html.contains("<td>NAME</td>"));
+        assert!(!html.contains("23 years"));
+    }
+
+    #[test]
+    fn test_boolean_icons() {
+        let infobox_contents = r##"
+# Sunshine
+## Magic
+yes
+
+## Royalty
+No
+
+## Name
+Testing
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { boolean_icons: true, ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<td><span class="infobox-boolean" title="yes">✓</span></td>"##));
+        assert!(html.contains(r##"<td><span class="infobox-boolean" title="No">✗</span></td>"##));
+        assert!(html.contains("<td>Testing</td>"));
+
+        let config = InfoboxConfig { boolean_icons: true, true_icon: "Y".into(), ..Default::default() };
+        assert!(infobox.render_html(&config).contains(r##"<span class="infobox-boolean" title="yes">Y</span>"##));
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("infobox-boolean"));
+    }
+
+    #[test]
+    fn test_from_markdown_contents_setext_headings() {
+        let infobox_contents = r##"
+Sunshine
+========
+
+Name
+----
+Testing
+
+Age
+---
+23 years
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let fields: Vec<_> = infobox.sections.iter()
+            .map(|section| match section {
+                InfoboxSection::Field(field) => (field.name.as_str(), field.contents.as_str()),
+                section => panic!("unexpected section: {:?}", section),
+            })
+            .collect();
+
+        assert_eq!("Sunshine", infobox.title);
+        assert_eq!(vec![("Name", "Testing"), ("Age", "23 years")], fields);
+    }
+
+    #[test]
+    fn test_schema_type() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+
+## Birth date
+1999-07-27
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { schema_type: Some("Person".into()), ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<table class="infobox" id="infobox-sunshine" itemscope itemtype="https://schema.org/Person">"##));
+        assert!(html.contains(r##"<td itemprop="name">Testing</td>"##));
+        assert!(html.contains(r##"<td itemprop="birthDate">1999-07-27</td>"##));
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("itemprop"));
+    }
+
+    #[test]
+    fn test_book_language() {
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book("{{#infobox}}\n# Sunshine\n{{/infobox}}")).unwrap();
+
+        assert!(chapter_content(&book).contains(r##"lang="en""##));
+        assert!(!Infobox::from_markdown_content("# Sunshine").unwrap()
+            .render_html(&InfoboxConfig::default())
+            .contains("lang="));
+    }
+
+    #[test]
+    fn test_kbd_fields() {
+        let infobox_contents = r##"
+# Copy
+## Shortcut
+`Ctrl+C`
+
+## Command
+`cp`
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { kbd_fields: vec!["Shortcut".into()], ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains("<td><kbd>Ctrl+C</kbd></td>"));
+        assert!(html.contains("<td><code>cp</code></td>"));
+    }
+
+    #[test]
+    fn test_render_many_fields() {
+        let infobox_contents: String = std::iter::once("# Sunshine\n".to_string())
+            .chain((0..1000).map(|i| format!("## Field {}\nValue {}\n", i, i)))
+            .collect();
+
+        let start = std::time::Instant::now();
+        let infobox = Infobox::from_markdown_content(&infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(1000, html.matches("<td>Value ").count());
+        assert!(html.contains("<td>Field 999</td>\n    <td>Value 999</td>"));
+    }
+
+    #[test]
+    fn test_tag_fields() {
+        let infobox_contents = r##"
+# Sunshine
+## Traits
+curious, brave
+
+## Skills
+- magic
+- cooking
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { tag_fields: vec!["Traits".into(), "Skills".into()], ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<td><span class="infobox-tag">curious</span> <span class="infobox-tag">brave</span></td>"##));
+        assert!(html.contains(r##"<td><span class="infobox-tag">magic</span> <span class="infobox-tag">cooking</span></td>"##));
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Traits\n[curious, very](a,b.md), brave").unwrap();
+        let config = InfoboxConfig { tag_fields: vec!["Traits".into()], ..Default::default() };
+        assert!(infobox.render_html(&config).contains(concat!(
+            r##"<td><span class="infobox-tag"><a href="a,b.md">curious, very</a></span> "##,
+            r##"<span class="infobox-tag">brave</span></td>"##,
+        )));
+    }
+
+    #[test]
+    fn test_progress_fields() {
+        let infobox_contents = r##"
+# Sunshine
+## Training
+75%
+
+## Studies
+unknown
+
+## Overtime
+150%
+
+## Debt
+-5%
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let progress_fields = vec!["Training".into(), "Studies".into(), "Overtime".into(), "Debt".into()];
+        let html = infobox.render_html(&InfoboxConfig { progress_fields, ..Default::default() });
+
+        assert!(html.contains(r##"<td><progress value="75" max="100">75%</progress></td>"##));
+        assert!(html.contains("<td>unknown</td>"));
+        assert!(html.contains("<td>150%</td>"));
+        assert!(html.contains("<td>-5%</td>"));
+    }
+
+    #[test]
+    fn test_disabled_infobox() {
+        let chapter_contents = r##"
+{{#infobox disabled}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert_eq!("\n\n# Sunshine\n## Name\nTesting\n\n", chapter_content(&book));
+    }
+
+    #[test]
+    fn test_max_field_length() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+
+## Nickname
+Sunshine, the little one
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { max_field_length: Some(12), ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<td><span title="Sunshine, the little one">Sunshine, th…</span></td>"##));
+        assert!(html.contains("<td>Testing</td>"));
+    }
+
+    #[test]
+    fn test_zoomable_images() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg)").unwrap();
+
+        let config = InfoboxConfig { zoomable_images: true, ..Default::default() };
+        assert!(infobox.render_html(&config).contains(r##"<img src="images/test.jpg" alt="image" title="image" data-zoomable/>"##));
+
+        let config = InfoboxConfig { zoomable_images: true, zoom_attribute: "data-lightbox".into(), ..Default::default() };
+        assert!(infobox.render_html(&config).contains(r##"<img src="images/test.jpg" alt="image" title="image" data-lightbox/>"##));
+
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("data-zoomable"));
+    }
+
+    #[test]
+    fn test_title_anchor() {
+        let infobox = Infobox::from_markdown_content("# Sunshine").unwrap();
+        let config = InfoboxConfig { title_anchor: true, ..Default::default() };
+
+        assert!(infobox.render_html(&config)
+            .contains(r##"<th colspan="2"><a class="header" href="#infobox-sunshine">Sunshine</a></th>"##));
+    }
+
+    #[test]
+    fn test_mermaid_field() {
+        let infobox_contents = r##"
+# Sunshine
+## Family
+```mermaid
+graph TD
+    Plum --> Sunshine
+```
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td>\n\n```mermaid\ngraph TD\n    Plum --> Sunshine\n```\n\n</td>"));
+    }
+
+    #[test]
+    fn test_lead_paragraph() {
+        let infobox_contents = r##"
+# Sunshine
+The lost princess.
+
+## Name
+Testing
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { lead_paragraph: true, ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        let lead = html.find(r##"<tr class="infobox-lead">
+    <td colspan="2">The lost princess.</td>
+</tr>"##).unwrap();
+        assert!(lead < html.find("<td>Name</td>").unwrap());
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("The lost princess."));
+    }
+
+    #[test]
+    fn test_image_aspect_ratio() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image|aspect-ratio=4/3](images/test.jpg)").unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<img src="images/test.jpg" alt="image" title="image" style="aspect-ratio: 4/3"/>"##));
+
+        let infobox_contents = r##"# Sunshine
+![image|aspect-ratio=1" onmouseover="alert(1)](images/test.jpg)"##;
+        let error = Infobox::from_markdown_content(infobox_contents).unwrap_err();
+        assert!(format!("{:#}", error).contains("invalid image aspect ratio"));
+    }
+
+    #[test]
+    fn test_emit_partials() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let root = tempfile::tempdir().unwrap();
+        let mut ctx = mock_context_with_config("html", json!({ "emit-partials": true }));
+        ctx.root = root.path().to_owned();
+
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert_eq!("\n{{> infobox-sunshine}}\n", chapter_content(&book));
+
+        let partial = fs::read_to_string(root.path().join("theme/partials/infobox-sunshine.hbs")).unwrap();
+        assert!(partial.starts_with("<table"));
+        assert!(partial.contains("<td>Testing</td>"));
+    }
+
+    #[test]
+    fn test_preprocessor() {
+        let chapter_contents = r##"
+# Sunshine
+
+{{#infobox}}
+# Sunshine
+![image](images/test.jpg)
+
+## Name
+Testing
+{{/infobox}}
+
+# History
+Teste
+"##;
+
+        let expected_contents = r##"
+# Sunshine
+
+<table class="infobox" id="infobox-sunshine" lang="en">
+<thead>
+<tr>
+<th colspan="2">Sunshine</th>
+</tr>
+</thead>
+
+<tr>
+    <td colspan="2"><img src="images/test.jpg" alt="image" title="image"/></td>
+</tr>
+
+<tr>
+    <td>Name</td>
+    <td>Testing</td>
+</tr>
+</table>
+
+# History
+Teste
+"##;
+
+        let ctx = mock_context("html");
+        let book = mock_book(chapter_contents);
+        let expected_book = mock_book(expected_contents);
+
+        assert_eq!(expected_book, InfoboxPreprocessor.run(&ctx, book).unwrap());
+    }
+
+    #[test]
+    fn test_preprocessor_print_friendly() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "print-friendly": true }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&book).contains(r##"<table class="infobox infobox-print" id="infobox-sunshine" lang="en">"##));
+    }
+
+    #[test]
+    fn test_nbsp_units() {
+        let infobox_contents = r##"
+# Sunshine
+## Age
+23 years
+
+## Weight
+5 kg, 12.5 lb
+
+## Command
+`sleep 5 s` for 5 s
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { nbsp_units: true, ..Default::default() });
+
+        assert!(html.contains("<td>23&nbsp;years</td>"));
+        assert!(html.contains("<td>5&nbsp;kg, 12.5&nbsp;lb</td>"));
+        assert!(html.contains("<td><code>sleep 5 s</code> for 5&nbsp;s</td>"));
+    }
+
+    #[test]
+    fn test_title_deeper_than_fields() {
+        let infobox_contents = r##"
+### Sunshine
+## Name
+Testing
+"##;
+
+        let error = Infobox::from_markdown_content(infobox_contents).unwrap_err();
+        assert!(error.to_string().contains("field heading (h2) is shallower than the infobox title heading (h3)"));
+
+        let infobox_contents = r##"
+## Sunshine
+## Name
+Testing
+"##;
+
+        assert!(Infobox::from_markdown_content(infobox_contents).is_ok());
+    }
+
+    #[test]
+    fn test_emit_jsonld() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+
+## Age
+23
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { emit_jsonld: true, ..Default::default() });
+
+        let script = html.split(r##"<script type="application/ld+json">"##).nth(1).unwrap();
+        let data: serde_json::Value = serde_json::from_str(script.strip_suffix("</script>").unwrap()).unwrap();
+        assert_eq!(data, json!({
+            "@context": "https://schema.org",
+            "@type": "Thing",
+            "name": "Sunshine",
+            "Name": "Testing",
+            "Age": "23",
+        }));
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("<script"));
+    }
+
+    #[test]
+    fn test_image_border_and_shadow() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg)").unwrap();
+        let config = InfoboxConfig {
+            image_border: Some("1px solid #ccc".into()),
+            image_shadow: Some("0 2px 4px rgba(0, 0, 0, 0.2)".into()),
+            ..Default::default()
+        };
+
+        assert!(infobox.render_html(&config).contains(
+            r##"<img src="images/test.jpg" alt="image" title="image" style="border: 1px solid #ccc; box-shadow: 0 2px 4px rgba(0, 0, 0, 0.2)"/>"##
+        ));
+    }
+
+    #[test]
+    fn test_blank_lines_between_fields() {
+        let parse = |separator: &str| {
+            let infobox_contents = [
+                "# Sunshine\n## Name\nTesting",
+                "## Age\n23",
+                "![image](images/test.jpg)",
+                "## Hobbies\n* Painting\n* Reading",
+                "## Height\n160 cm",
+            ].join(&format!("\n{}", separator));
+
+            without_ranges(Infobox::from_markdown_content(&infobox_contents).unwrap())
+        };
+
+        let infobox = parse("");
+        assert_eq!(5, infobox.sections.len());
+        assert_eq!(infobox, parse("\n"));
+        assert_eq!(infobox, parse("\n\n"));
+    }
+
+    fn without_ranges(mut infobox: Infobox) -> Infobox {
+        for section in &mut infobox.sections {
+            match section {
+                InfoboxSection::Image(image) => image.range = 0..0,
+                InfoboxSection::Gallery(gallery) => {
+                    gallery.range = 0..0;
+                    gallery.images.iter_mut().for_each(|image| image.range = 0..0);
+                },
+                InfoboxSection::Field(field) => field.range = 0..0,
+                InfoboxSection::Description(description) => description.range = 0..0,
+            }
+        }
+
+        infobox
+    }
+
+    #[test]
+    fn test_show_updated() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("src")).unwrap();
+        let source = fs::File::create(root.path().join("src/chapter_1.md")).unwrap();
+        source.set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000)).unwrap();
+
+        let mut ctx = mock_context_with_config("html", json!({ "show-updated": true }));
+        ctx.root = root.path().to_owned();
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert!(chapter_content(&book).contains(r##"<td colspan="2">Last updated <time datetime="2023-11-14">2023-11-14</time></td>"##));
+
+        let chapter_contents = format!("---\ndate: 2024-05-01\n---\n{}", chapter_contents);
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(&chapter_contents)).unwrap();
+        assert!(chapter_content(&book).contains(r##"<time datetime="2024-05-01">2024-05-01</time>"##));
+
+        let chapter_contents = chapter_contents.replace("2024-05-01", r##"2024" onclick="alert(1)"##);
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(&chapter_contents)).unwrap();
+        assert!(chapter_content(&book).contains(r##"<time datetime="2024&quot; onclick=&quot;alert(1)">"##));
+    }
+
+    #[test]
+    fn test_max_images() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+![first](images/1.jpg)
+
+![second](images/2.jpg)
+
+![third](images/3.jpg)
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "max-images": 2 }));
+        let error = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap_err();
+        assert!(format!("{:#}", error).contains(r##"infobox "Sunshine" has 3 images, more than the maximum of 2"##));
+
+        let ctx = mock_context_with_config("html", json!({ "max-images": 2, "max-images-behavior": "truncate" }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert!(chapter_content(&book).contains("images/2.jpg"));
+        assert!(!chapter_content(&book).contains("images/3.jpg"));
+    }
+
+    #[test]
+    fn test_color_swatch() {
+        let infobox_contents = r##"
+# Sunshine
+## Hair color
+#ff8800
+
+## Eye color
+Blue
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { color_swatch: true, ..Default::default() });
+
+        assert!(html.contains(r##"<td><span class="infobox-swatch" style="background-color: #ff8800"></span> #ff8800</td>"##));
+        assert!(html.contains("<td>Blue</td>"));
+    }
+
+    #[test]
+    fn test_footer_html() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Name\nTesting").unwrap();
+        let config = InfoboxConfig { footer_html: Some("<small>Fan-made content</small>".into()), ..Default::default() };
+
+        assert!(infobox.render_html(&config).contains(r##"<tfoot>
+<tr class="infobox-footer">
+    <td colspan="2"><small>Fan-made content</small></td>
+</tr>
+</tfoot>"##));
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("<tfoot>"));
+    }
+
+    #[test]
+    fn test_image_data_uri() {
+        let data_uri = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+        let infobox = Infobox::from_markdown_content(&format!("# Sunshine\n![pixel]({})", data_uri)).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(&format!(r##"<img src="{}" alt="pixel" title="pixel"/>"##, data_uri)));
+    }
+
+    #[test]
+    fn test_template_ref() {
+        let chapter_contents = r##"
+{{#infobox template-ref="card"}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({
+            "templates": {
+                "card": r##"<div class="card" id="{id}"><h3>{title}</h3><table>{rows}</table></div>"##,
+                "plain": "<table>{rows}</table>",
+            }
+        }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert_eq!(chapter_content(&book), r##"
+<div class="card" id="infobox-sunshine"><h3>Sunshine</h3><table>
+<tr>
+    <td>Name</td>
+    <td>Testing</td>
+</tr></table></div>
+"##);
+
+        let ctx = mock_context("html");
+        let error = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap_err();
+        assert!(format!("{:#}", error).contains(r##"unknown infobox template "card""##));
+    }
+
+    #[test]
+    fn test_replace_ranges() {
+        let content = "{{#infobox}}\n# Sunshine\n{{/infobox}}\n\nBetween the infoboxes\n\n{{#infobox}}\n# Luna\n{{/infobox}}\nAfter";
+        let range = |infobox: &str| {
+            let start = content.find(infobox).unwrap();
+            start..start + infobox.len()
+        };
+        let replacements = vec![
+            (range("{{#infobox}}\n# Sunshine\n{{/infobox}}"), "<table>A much longer rendered infobox</table>".to_owned()),
+            (range("{{#infobox}}\n# Luna\n{{/infobox}}"), "<table/>".to_owned()),
+        ];
+
+        assert_eq!(
+            "<table>A much longer rendered infobox</table>\n\nBetween the infoboxes\n\n<table/>\nAfter",
+            replace_ranges(content, replacements),
+        );
+    }
+
+    #[test]
+    fn test_preprocessor_multiple_infoboxes() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+
+Between the infoboxes
+
+{{#infobox}}
+# Luna
+## Name
+Testing
+
+## Age
+23
+{{/infobox}}
+
+After the infoboxes
+"##;
+        let sunshine = Infobox::from_markdown_content("# Sunshine\n## Name\nTesting").unwrap();
+        let luna = Infobox::from_markdown_content("# Luna\n## Name\nTesting\n\n## Age\n23").unwrap();
+        let config = InfoboxConfig { language: Some("en".into()), ..Default::default() };
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert_eq!(
+            format!(
+                "\n{}\n\nBetween the infoboxes\n\n{}\n\nAfter the infoboxes\n",
+                sunshine.render_html(&config),
+                luna.render_html(&config),
+            ),
+            chapter_content(&book),
+        );
+
+        let ctx = mock_context_with_config("html", json!({ "trim-surrounding-blanks": true }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book("{{#infobox}}\n# Sunshine\n{{/infobox}}\n\n\n{{#infobox}}\n# Luna\n{{/infobox}}")).unwrap();
+        assert_eq!(
+            format!(
+                "{}\n\n{}\n",
+                Infobox::from_markdown_content("# Sunshine").unwrap().render_html(&config),
+                Infobox::from_markdown_content("# Luna").unwrap().render_html(&config),
+            ),
+            chapter_content(&book),
+        );
+    }
+
+    #[test]
+    fn test_wiki_links() {
+        let infobox_contents = r##"
+# Sunshine
+## Family
+[[Some Page]] and [[Plum Priestess|her mother]]
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { wiki_links: true, ..Default::default() });
+
+        assert!(html.contains(r##"<td><a href="some-page.md">Some Page</a> and <a href="plum-priestess.md">her mother</a></td>"##));
+
+        let chapter = Chapter::new("Sunshine", String::new(), "people/sunshine.md", Vec::new());
+        let config = InfoboxConfig { wiki_links: true, ..Default::default() };
+        let html = infobox.render_chapter_html(&config, Some(&chapter), None, &InfoboxAttributes::new());
+        assert!(html.contains(r##"<a href="../some-page.md">Some Page</a>"##));
+    }
+
+    #[test]
+    fn test_image_open_in_new_tab() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image|full=images/full.jpg](images/test.jpg)").unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig { open_in_new_tab: true, ..Default::default() }).contains(
+            r##"<a href="images/full.jpg" target="_blank" rel="noopener"><img src="images/test.jpg" alt="image" title="image"/></a>"##
+        ));
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<a href="images/full.jpg"><img src="images/test.jpg" alt="image" title="image"/></a>"##));
+    }
+
+    #[test]
+    fn test_escape_field_name_and_contents() {
+        let infobox_contents = r##"
+# Sunshine
+## Q&A <3
+a < b & c
+
+## Shortcut
+`<Ctrl> & <C>`
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td>Q&amp;A &lt;3</td>"));
+        assert!(html.contains("<td>a &lt; b &amp; c</td>"));
+        assert!(html.contains("<td><code>&lt;Ctrl&gt; &amp; &lt;C&gt;</code></td>"));
+
+        let config = InfoboxConfig { escape_style: EscapeStyle::Numeric, ..Default::default() };
+        let infobox = Infobox::from_markdown_content_with_config(infobox_contents, &config).unwrap();
+        assert!(infobox.render_html(&config).contains("<td>a &#60; b &#38; c</td>"));
+    }
+
+    #[test]
+    fn test_escape_title() {
+        let infobox = Infobox::from_markdown_content("# Tom & Jerry <v2>\n## Name\nTesting").unwrap();
+        assert_eq!("Tom & Jerry <v2>", infobox.title);
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<th colspan="2">Tom &amp; Jerry &lt;v2&gt;</th>"##));
+    }
+
+    #[test]
+    fn test_nav_footer() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "nav-footer": true }));
+        let book = mock_book_with_chapters(&["# Luna", chapter_contents, "# Ariel"]);
+        let book = InfoboxPreprocessor.run(&ctx, book).unwrap();
+
+        let middle = match &book.sections[1] {
+            BookItem::Chapter(chapter) => &chapter.content,
+            _ => panic!("expected a chapter"),
+        };
+        assert!(middle.contains(r##"<tr class="infobox-nav">
+    <td><a href="chapter_1.md">← Chapter 1</a></td>
+    <td><a href="chapter_3.md">Chapter 3 →</a></td>
+</tr>"##));
+    }
+
+    #[test]
+    fn test_image_attributes_escaped() {
+        let infobox = Infobox::from_markdown_content(r##"# Sunshine
+![image](<images/test".jpg> 'a "quoted" title')"##).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<img src="images/test&quot;.jpg" alt="image" title="a &quot;quoted&quot; title"/>"##));
+    }
+
+    #[test]
+    fn test_field_inline_formatting() {
+        let infobox_contents = r##"
+# Sunshine
+## Birthplace
+Born in **Plum**, *near* the [old castle](castle.md) ~~twice~~
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains(
+            r##"<td>Born in <strong>Plum</strong>, <em>near</em> the <a href="castle.md">old castle</a> <del>twice</del></td>"##
+        ));
+    }
+
+    #[test]
+    fn test_field_line_break_marker() {
+        let infobox_contents = r##"
+# Sunshine
+## Home
+Plum Castle<br>East Wing
+
+## Work
+Bakery \\ Night shift
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { line_break_marker: Some(" \\ ".into()), ..Default::default() });
+
+        assert!(html.contains("<td>Plum Castle<br>East Wing</td>"));
+        assert!(html.contains("<td>Bakery<br>Night shift</td>"));
+    }
+
+    #[test]
+    fn test_ruby() {
+        let infobox_contents = r##"
+# Sunshine
+## Japanese name
+{漢字|かんじ} and {太陽|たいよう}
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig { ruby: true, ..Default::default() })
+            .contains("<td><ruby>漢字<rt>かんじ</rt></ruby> and <ruby>太陽<rt>たいよう</rt></ruby></td>"));
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains("<td>{漢字|かんじ} and {太陽|たいよう}</td>"));
+    }
+
+    #[test]
+    fn test_title_inline_formatting() {
+        let infobox = Infobox::from_markdown_content("# *Sunshine* (song)\n## Name\nTesting").unwrap();
+        assert_eq!("Sunshine (song)", infobox.title);
+        assert_eq!("infobox-sunshine-song", infobox.id());
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<th colspan="2"><em>Sunshine</em> (song)</th>"##));
+    }
+
+    #[test]
+    fn test_format_numbers() {
+        let infobox_contents = r##"
+# Sunshine
+## Population
+1000000
+
+## Founded
+1999-07-27, see [census](census-20240.md)
+
+## Port
+`listen 65535` on 65535
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { format_numbers: true, language: Some("en".into()), ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains("<td>1,000,000</td>"));
+        assert!(html.contains(r##"<td>1999-07-27, see <a href="census-20240.md">census</a></td>"##));
+        assert!(html.contains("<td><code>listen 65535</code> on 65,535</td>"));
+
+        let config = InfoboxConfig { format_numbers: true, language: Some("pt-BR".into()), ..Default::default() };
+        assert!(infobox.render_html(&config).contains("<td>1.000.000</td>"));
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let schema = InfoboxSchema {
+            required_fields: vec!["Name".into()],
+            optional_fields: vec!["Age".into()],
+            allow_images: false,
+        };
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Name\nTesting\n## Age\n23").unwrap();
+        assert!(infobox.validate(&schema).is_ok());
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Age\n23").unwrap();
+        assert_eq!(r##"infobox "Sunshine" is missing required field "Name""##, infobox.validate(&schema).unwrap_err().to_string());
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Name\nTesting\n## Height\n160 cm").unwrap();
+        assert_eq!(r##"infobox "Sunshine" has unexpected field "Height""##, infobox.validate(&schema).unwrap_err().to_string());
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg)\n## Name\nTesting").unwrap();
+        assert!(infobox.validate(&schema).is_err());
+        assert!(infobox.validate(&InfoboxSchema { allow_images: true, ..schema }).is_ok());
+    }
+
+    #[test]
+    fn test_field_lists() {
+        let infobox_contents = r##"
+# Sunshine
+## Members
+- Luna
+- Ariel
+
+## Ranking
+1. Atlas
+2. Sunshine
+
+## Finalists
+3. Luna
+4. Ariel
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td><ul><li>Luna</li><li>Ariel</li></ul></td>"));
+        assert!(html.contains("<td><ol><li>Atlas</li><li>Sunshine</li></ol></td>"));
+        assert!(html.contains(r##"<td><ol start="3"><li>Luna</li><li>Ariel</li></ol></td>"##));
+    }
+
+    #[test]
+    fn test_field_code() {
+        let infobox_contents = r##"
+# Sunshine
+## Syntax
+`foo(bar)`
+
+## Example
+```rust
+if a < b {
+    foo(bar);
+}
+```
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td><code>foo(bar)</code></td>"));
+        assert!(html.contains(r##"<td><pre><code class="language-rust">if a &lt; b {&#10;    foo(bar);&#10;}&#10;</code></pre></td>"##));
+
+        // A blank line in the code would otherwise end the html block mid-table
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Example\n```\nfn a() {}\n\nfn b() {}\n```").unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td><pre><code>fn a() {}&#10;&#10;fn b() {}&#10;</code></pre></td>"));
+    }
+
+    #[test]
+    fn test_image_alt_fallback() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![Portrait of Sunshine](images/test.jpg)").unwrap();
+        let html = infobox.render_html(&InfoboxConfig { show_alt_fallback: true, ..Default::default() });
+
+        assert!(html.contains(concat!(
+            r##"<img src="images/test.jpg" alt="Portrait of Sunshine" title="Portrait of Sunshine" onerror="this.hidden=true;this.nextElementSibling.hidden=false"/>"##,
+            r##"<span class="infobox-image-fallback" hidden>Portrait of Sunshine</span>"##,
+        )));
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("infobox-image-fallback"));
+    }
+
+    #[test]
+    fn test_drop_empty_fields() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+
+## Nickname
+
+## Age
+N/A
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "drop-empty-fields": true, "null-token": "N/A" }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let content = chapter_content(&book);
+
+        assert!(content.contains("<td>Testing</td>"));
+        assert!(!content.contains("<td>Nickname</td>"));
+        assert!(!content.contains("<td>Age</td>"));
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert!(chapter_content(&book).contains("<td>Nickname</td>"));
+    }
+
+    #[test]
+    fn test_field_table() {
+        let infobox_contents = r##"
+# Sunshine
+## Stats
+| Stat | Value |
+|------|-------|
+| Strength | 10 |
+| Speed | 7 |
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains(concat!(
+            "<td><table>",
+            "<thead><tr><th>Stat</th><th>Value</th></tr></thead>",
+            "<tbody><tr><td>Strength</td><td>10</td></tr><tr><td>Speed</td><td>7</td></tr></tbody>",
+            "</table></td>",
+        )));
+    }
+
+    #[test]
+    fn test_field_paragraphs() {
+        let infobox_contents = r##"
+# Sunshine
+## Biography
+Born in the Plum Principality.
+
+Later moved to the coast.
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains("<td><p>Born in the Plum Principality.</p><p>Later moved to the coast.</p></td>"));
+    }
+
+    #[test]
+    fn test_header_cells() {
+        let infobox = Infobox::from_markdown_content("# Sunshine | Luna\n## Age\n23").unwrap();
+        let config = InfoboxConfig { header_cells: 3, ..Default::default() };
+
+        assert!(infobox.render_html(&config).contains("<tr>\n<th>Sunshine</th><th>Luna</th><th></th>\n</tr>"));
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains(r##"<th colspan="2">Sunshine | Luna</th>"##));
+    }
+
+    #[test]
+    fn test_css_vars() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({
+            "css-vars": { "infobox-accent": "#f80", "--infobox-width": "20em" }
+        }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&book).contains(
+            r##"<table class="infobox" id="infobox-sunshine" lang="en" style="--infobox-width: 20em; --infobox-accent: #f80">"##
+        ));
+    }
+
+    #[test]
+    fn test_field_line_breaks() {
+        let infobox_contents = "# Sunshine\n## Quote\nFirst line  \nsecond line\nthird line";
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains("<td>First line<br>second line\nthird line</td>"));
+
+        let config = InfoboxConfig { soft_break_as_br: true, ..Default::default() };
+        let infobox = Infobox::from_markdown_content_with_config(infobox_contents, &config).unwrap();
+        assert!(infobox.render_html(&config).contains("<td>First line<br>second line<br>third line</td>"));
+    }
+
+    #[test]
+    fn test_field_blockquote() {
+        let infobox_contents = r##"
+# Sunshine
+## Motto
+> Always *shine*
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains("<td><blockquote>Always <em>shine</em></blockquote></td>"));
+    }
+
+    #[test]
+    fn test_rating_fields() {
+        let infobox_contents = r##"
+# Sunshine
+## Cooking
+4/5
+
+## Singing
+6/5
+
+## Dancing
+3/1000000000
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { rating_fields: vec!["Cooking".into(), "Singing".into(), "Dancing".into()], ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<td><span class="infobox-rating" title="4/5">★★★★☆</span></td>"##));
+        assert!(html.contains("<td>6/5</td>"));
+        assert!(html.contains("<td>3/1000000000</td>"));
+    }
+
+    #[test]
+    fn test_debug_comments() {
+        let chapter_contents = r##"
+{{#infobox disabled}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "debug-comments": true }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert_eq!("\n\n<!-- infobox skipped: disabled -->\n# Sunshine\n## Name\nTesting\n\n", chapter_content(&book));
+    }
+
+    #[test]
+    fn test_from_capture() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+"##;
+        let chapter_contents = format!("{{{{#infobox}}}}{}{{{{/infobox}}}}", infobox_contents);
+
+        let capture = infobox_regex(DEFAULT_TAG).captures(&chapter_contents).unwrap();
+
+        assert_eq!(
+            Infobox::from_markdown_content(infobox_contents).unwrap(),
+            Infobox::from_capture(capture).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_malformed_image() {
+        let image = Tag::Image(pulldown_cmark::LinkType::Inline, "sunshine.png".into(), "".into());
+        let mut iter = vec![
+            (Event::Start(image), 0..17),
+            (Event::Text("Sunshine".into()), 2..10),
+            (Event::End(Tag::Paragraph), 17..17),
+        ].into_iter().peekable();
+
+        let error = Infobox::parse_infobox_image(&mut iter).unwrap_err();
+
+        assert!(error.to_string().contains(r#"malformed image "sunshine.png""#));
+    }
+
+    #[test]
+    fn test_description_list_fields() {
+        let infobox_contents = r##"
+# Sunshine
+Name
+: Testing
+Nickname
+: *Sunny*
+"##;
+
+        let config = InfoboxConfig { extensions: vec![Extension::DescriptionLists], ..Default::default() };
+        let infobox = Infobox::from_markdown_content_with_config(infobox_contents, &config).unwrap();
+        let html = infobox.render_html(&config);
+
+        assert_eq!((0, 2), infobox.counts());
+        assert!(html.contains("<td>Testing</td>"));
+        assert!(html.contains("<td><em>Sunny</em></td>"));
+    }
+
+    #[test]
+    fn test_unknown_attributes() {
         let chapter_contents = r##"
+{{#infobox foo="bar" pinned}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context("html");
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let content = chapter_content(&book);
+
+        assert!(content.contains(r##"lang="en" data-foo="bar" data-pinned>"##));
+    }
+
+    #[test]
+    fn test_image_alt_and_title() {
+        let config = InfoboxConfig { image_title_fallback: ImageTitleFallback::None, ..Default::default() };
+
+        let infobox = Infobox::from_markdown_content(r##"# Sunshine
+![Sunshine as a child](images/test.jpg "Portrait, 2009")"##).unwrap();
+        assert!(infobox.render_html(&config)
+            .contains(r##"<img src="images/test.jpg" alt="Sunshine as a child" title="Portrait, 2009"/>"##));
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![Sunshine as a child](images/test.jpg)").unwrap();
+        assert!(infobox.render_html(&config)
+            .contains(r##"<img src="images/test.jpg" alt="Sunshine as a child"/>"##));
+    }
+
+    #[test]
+    fn test_image_size() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image|width=200|height=150px](images/test.jpg)").unwrap();
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<img src="images/test.jpg" alt="image" title="image" width="200" height="150"/>"##));
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n![image](images/test.jpg)").unwrap();
+        assert!(!infobox.render_html(&InfoboxConfig::default()).contains("width="));
+
+        assert!(Infobox::from_markdown_content("# Sunshine\n![image|width=wide](images/test.jpg)").is_err());
+    }
+
+    #[test]
+    fn test_collapsible() {
+        let infobox = Infobox::from_markdown_content("# Sunshine *Bright*\n## Name\nTesting").unwrap();
+        let html = infobox.render_html(&InfoboxConfig { collapsible: true, ..Default::default() });
+
+        assert!(html.starts_with(concat!(
+            r##"<details class="infobox-collapsible" open>"##, "\n",
+            "<summary>Sunshine <em>Bright</em></summary>\n",
+            r##"<table class="infobox""##,
+        )));
+        assert!(html.ends_with("</table>\n</details>"));
+    }
+
+    #[test]
+    fn test_image_semantics_per_image() {
+        let infobox_contents = r##"
+# Sunshine
+![Sunshine as a child|semantics=figure](images/child.jpg)
+![|semantics=figure](images/adult.jpg "Sunshine today")
+![Sunshine's house](images/house.jpg)
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<figcaption>Sunshine as a child</figcaption></figure>"));
+        assert!(html.contains("<figcaption>Sunshine today</figcaption></figure>"));
+        assert_eq!(2, html.matches("<figure>").count());
+    }
+
+    #[test]
+    fn test_field_align() {
+        let infobox_contents = r##"
+# Sunshine
+## Score {align=right}
+42
+
+## Name
+Testing
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td>Score</td>\n    <td style=\"text-align:right\">42</td>"));
+        assert!(html.contains("<td>Name</td>\n    <td>Testing</td>"));
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Score {align=up}\n42\n\n## Set {A}\nB").unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+        assert!(html.contains("<td>Score {align=up}</td>\n    <td>42</td>"));
+        assert!(html.contains("<td>Set {A}</td>\n    <td>B</td>"));
+    }
+
+    #[test]
+    fn test_image_gallery() {
+        let infobox_contents = r##"
+# Sunshine
+![Sunshine as a child](images/child.jpg)
+![Sunshine today](images/adult.jpg)
+
+![Sunshine's house](images/house.jpg)
+
+## Name
+Testing
+
+![Sunshine's cat](images/cat.jpg)
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert_eq!((4, 1), infobox.counts());
+        assert_eq!(1, html.matches(r##"<tr class="infobox-gallery">"##).count());
+        let gallery = html.split(r##"<tr class="infobox-gallery">"##).nth(1).unwrap();
+        let gallery = &gallery[..gallery.find("</tr>").unwrap()];
+        assert_eq!(3, gallery.matches("<img ").count());
+        assert!(!gallery.contains("images/cat.jpg"));
+    }
+
+    #[test]
+    fn test_summary_fields() {
+        let infobox_contents = r##"
+# Sunshine
+## Name
+Testing
+
+## TL;DR
+A *bright* test subject
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let config = InfoboxConfig { summary_fields: Vec::new(), ..Default::default() };
+        assert!(infobox.render_html(&config).contains("<td>TL;DR</td>"));
+
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        let summary_row = r##"<tr class="infobox-summary">
+    <td colspan="2">A <em>bright</em> test subject</td>
+</tr>"##;
+        assert!(html.contains(&format!("</thead>\n\n{}", summary_row)));
+        assert!(!html.contains("<td>TL;DR</td>"));
+        assert!(html.find(summary_row).unwrap() < html.find("<td>Name</td>").unwrap());
+    }
+
+    #[test]
+    fn test_framed_images() {
+        let infobox_contents = r##"
 # Sunshine
+![Sunshine as a child](images/child.jpg)
+
+## Name
+Testing
+
+![](images/house.jpg)
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { framed_images: true, ..Default::default() });
+
+        assert!(html.contains(concat!(
+            r##"<td colspan="2"><figure class="infobox-frame"><img src="images/child.jpg" alt="Sunshine as a child" title="Sunshine as a child"/>"##,
+            r##"<figcaption>Sunshine as a child</figcaption></figure></td>"##,
+        )));
+        assert!(html.contains(r##"<td colspan="2"><img src="images/house.jpg"/></td>"##));
+    }
 
+    #[test]
+    fn test_custom_class() {
+        let chapter_contents = r##"
 {{#infobox}}
 # Sunshine
-![image](images/test.jpg)
+## Name
+Testing
+{{/infobox}}
+"##;
 
+        let ctx = mock_context_with_config("html", json!({ "class": "character-card", "print-friendly": true }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&book).contains(r##"<table class="character-card infobox-print" id="infobox-sunshine""##));
+    }
+
+    #[test]
+    fn test_custom_tag() {
+        let chapter_contents = r##"
+{{#box}}
+# Sunshine
 ## Name
 Testing
+{{/box}}
+
+{{#infobox}}
+# Luna
 {{/infobox}}
+"##;
 
-# History
-Teste
+        let ctx = mock_context_with_config("html", json!({ "tag": "box" }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let content = chapter_content(&book);
+
+        assert!(content.contains(r##"id="infobox-sunshine""##));
+        assert!(content.contains("<td>Testing</td>"));
+        assert!(!content.contains("{{#box}}"));
+        assert!(content.contains("{{#infobox}}\n# Luna\n{{/infobox}}"));
+    }
+
+    #[test]
+    fn test_field_note_block() {
+        let infobox_contents = r##"
+# Sunshine
+## Birthday
+1999-07-27
+
+```note
+Celebrated on the 28th in leap years & holidays
+```
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains(concat!(
+            r##"<td>1999-07-27<div class="infobox-note">"##,
+            r##"Celebrated on the 28th in leap years &amp; holidays</div></td>"##,
+        )));
+
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Birthday\n1999-07-27\n\n```note\nFirst\n\nSecond\n```").unwrap();
+        assert!(infobox.render_html(&InfoboxConfig::default())
+            .contains(r##"<td>1999-07-27<div class="infobox-note">First&#10;&#10;Second</div></td>"##));
+    }
+
+    #[test]
+    fn test_align() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Name\nTesting").unwrap();
+
+        let html = infobox.render_html(&InfoboxConfig::default());
+        assert!(html.contains(r##"<table class="infobox" id="infobox-sunshine">"##));
+
+        let html = infobox.render_html(&InfoboxConfig { align: InfoboxAlign::Left, ..Default::default() });
+        assert!(html.contains(r##"<table class="infobox infobox-align-left" id="infobox-sunshine">"##));
+
+        let html = infobox.render_html(&InfoboxConfig { align: InfoboxAlign::None, ..Default::default() });
+        assert!(html.contains(r##"<table class="infobox infobox-align-none" id="infobox-sunshine">"##));
+
+        let config = InfoboxConfig { align: InfoboxAlign::Left, collapsible: true, print_friendly: true, ..Default::default() };
+        let html = infobox.render_html(&config);
+        assert!(html.starts_with(r##"<details class="infobox-collapsible infobox-print infobox-align-left" open>"##));
+        assert!(html.contains(r##"<table class="infobox" id="infobox-sunshine">"##));
+    }
+
+    #[test]
+    fn test_id_strategy_content_hash() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Name
+Testing
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("html", json!({ "id-strategy": "content-hash" }));
+        let first = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let second = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        assert!(chapter_content(&first).contains(r##"id="infobox-f9cabd8c62e022f9""##));
+        assert_eq!(chapter_content(&first), chapter_content(&second));
+    }
+
+    #[test]
+    fn test_field_dir() {
+        let infobox_contents = r##"
+# Sunshine
+## Arabic name {dir=rtl}
+شمس
+
+## Name
+Testing
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td>Arabic name</td>\n    <td dir=\"rtl\">شمس</td>"));
+        assert!(html.contains("<td>Name</td>\n    <td>Testing</td>"));
+    }
+
+    #[test]
+    fn test_markdown_table_layout() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+![Portrait](images/test.jpg)
+
+## Name
+Testing
+
+## Hobbies
+Painting | *Reading*
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("markdown", json!({ "layout": "markdown-table" }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+
+        let expected_table = r##"| Sunshine | |
+| --- | --- |
+| ![Portrait](<images/test.jpg>) | |
+| Name | Testing |
+| Hobbies | Painting \| <em>Reading</em> |"##;
+        assert_eq!(format!("\n{}\n", expected_table), chapter_content(&book));
+    }
+
+    #[test]
+    fn test_inline_css() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+{{/infobox}}
+
+{{#infobox}}
+# Luna
+{{/infobox}}
 "##;
 
+        let ctx = mock_context_with_config("html", json!({ "inline-css": true }));
+        let book = mock_book_with_chapters(&[chapter_contents, "# Ariel"]);
+        let book = InfoboxPreprocessor.run(&ctx, book).unwrap();
+
+        let with_infoboxes = chapter_content(&book);
+        assert_eq!(1, with_infoboxes.matches("<style>").count());
+        assert!(with_infoboxes.starts_with(&format!("<style>\n{}</style>\n", INFOBOX_CSS)));
+        let without_infoboxes = match &book.sections[1] {
+            BookItem::Chapter(chapter) => &chapter.content,
+            _ => panic!("expected a chapter"),
+        };
+        assert_eq!("# Ariel", without_infoboxes);
+
         let ctx = mock_context("html");
-        let book = mock_book(chapter_contents);
-        let expected_book = book.clone();
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        assert!(!chapter_content(&book).contains("<style>"));
+    }
 
-        assert_eq!(expected_book, InfoboxPreprocessor.run(&ctx, book).unwrap());
+    #[test]
+    fn test_single_bold_label_field() {
+        let infobox_contents = r##"
+# Sunshine
+## Height
+**Height:** 180cm
+
+## Build
+**180cm** tall
+**60kg** heavy
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig::default());
+
+        assert!(html.contains("<td><strong>Height:</strong> 180cm</td>"));
+        assert!(html.contains("<td><strong>180cm</strong> tall\n<strong>60kg</strong> heavy</td>"));
+        assert!(!html.contains("<dl>"));
+    }
+
+    #[test]
+    fn test_emit_jsonld_plain_text() {
+        let infobox_contents = r##"
+# *Sun* & Moon
+## Home
+**Tom** & [Jerry](jerry.md)
+"##;
+
+        let infobox = Infobox::from_markdown_content(infobox_contents).unwrap();
+        let html = infobox.render_html(&InfoboxConfig { emit_jsonld: true, ..Default::default() });
+
+        let script = html.split(r##"<script type="application/ld+json">"##).nth(1).unwrap();
+        let data: serde_json::Value = serde_json::from_str(script.strip_suffix("</script>").unwrap()).unwrap();
+        assert_eq!(data["name"], "Sun & Moon");
+        assert_eq!(data["Home"], "Tom & Jerry");
+    }
+
+    #[test]
+    fn test_max_field_length_entities() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Owner\nTom & Jerry & co").unwrap();
+        let config = InfoboxConfig { max_field_length: Some(5), ..Default::default() };
+        let html = infobox.render_html(&config);
+
+        assert!(html.contains(r##"<td><span title="Tom &amp; Jerry &amp; co">Tom &amp;…</span></td>"##));
+    }
+
+    #[test]
+    fn test_markdown_table_layout_field_contents() {
+        let chapter_contents = r##"
+{{#infobox}}
+# Sunshine
+## Motto
+Rise // Shine
+
+## Greeting
+```rust
+hello();
+world();
+```
+{{/infobox}}
+"##;
+
+        let ctx = mock_context_with_config("markdown", json!({ "layout": "markdown-table", "line-break-marker": "//" }));
+        let book = InfoboxPreprocessor.run(&ctx, mock_book(chapter_contents)).unwrap();
+        let content = chapter_content(&book);
+
+        assert!(content.contains("| Motto | Rise <br> Shine |"));
+        assert!(content.contains(r##"| Greeting | <pre><code class="language-rust">hello();&#10;world();&#10;</code></pre> |"##));
+    }
+
+    #[test]
+    fn test_infobox_ranges_tag_boundary() {
+        assert!(infobox_ranges("{{#infoboxes}}\n# Sunshine\n{{/infobox}}").is_empty());
+        assert_eq!(vec![0..45], infobox_ranges("{{#infobox disabled}}\n# Sunshine\n{{/infobox}}"));
+    }
+
+    #[test]
+    fn test_edit_url_template_escaped() {
+        let mut chapter = Chapter::new("Sunshine", String::new(), "tom\"&jerry.md", Vec::new());
+        chapter.source_path = chapter.path.clone();
+        let config = InfoboxConfig { edit_url_template: Some("https://example.com/edit?file={path}".into()), ..Default::default() };
+
+        assert_eq!(
+            r##" <a class="infobox-edit" href="https://example.com/edit?file=tom&quot;&amp;jerry.md">edit</a>"##,
+            render_edit_link_html(&config, Some(&chapter)),
+        );
+    }
+
+    #[test]
+    fn test_field_text_after_image() {
+        let infobox = Infobox::from_markdown_content("# Sunshine\n## Bio\nFirst part.\n\n![p](x.png)\n\nSecond part.").unwrap();
+
+        assert_eq!(1, infobox.sections.len());
+        assert!(infobox.render_html(&InfoboxConfig::default()).contains("Second part."));
+    }
+
+    #[test]
+    fn test_template_placeholders_in_values() {
+        let infobox = Infobox::from_markdown_content("# Sunshine {rows}\n## Name\nTesting").unwrap();
+        let html = infobox.render_template_html("<h2>{title}</h2>{rows}", &InfoboxConfig::default(), None);
+
+        assert!(html.starts_with("<h2>Sunshine {rows}</h2>"));
+        assert_eq!(1, html.matches("<td>Testing</td>").count());
+    }
+
+    fn chapter_content(book: &Book) -> &str {
+        match &book.sections[0] {
+            BookItem::Chapter(ch) => &ch.content,
+            item => panic!("unexpected book item: {:?}", item),
+        }
     }
 
-    // taken from mdbook-admonish
     fn mock_context(renderer: &str) -> PreprocessorContext {
+        mock_context_with_config(renderer, json!({}))
+    }
+
+    // taken from mdbook-admonish
+    fn mock_context_with_config(renderer: &str, infobox_config: serde_json::Value) -> PreprocessorContext {
         let value = json!({
             "root": "/path/to/book",
             "config": {
@@ -400,6 +4154,9 @@ Teste
                     "src": "src",
                     "title": "TITLE"
                 },
+                "preprocessor": {
+                    "infobox": infobox_config
+                },
             },
             "renderer": renderer,
             "mdbook_version": "0.4.21"
@@ -409,20 +4166,27 @@ Teste
     }
 
     fn mock_book(content: &str) -> Book {
-        serde_json::from_value(json!({
-            "sections": [
-                {
-                    "Chapter": {
-                        "name": "Chapter 1",
-                        "content": content,
-                        "number": [1],
-                        "sub_items": [],
-                        "path": "chapter_1.md",
-                        "source_path": "chapter_1.md",
-                        "parent_names": []
-                    }
+        mock_book_with_chapters(&[content])
+    }
+
+    fn mock_book_with_chapters(contents: &[&str]) -> Book {
+        let sections: Vec<_> = contents.iter()
+            .enumerate()
+            .map(|(i, content)| json!({
+                "Chapter": {
+                    "name": format!("Chapter {}", i + 1),
+                    "content": content,
+                    "number": [i + 1],
+                    "sub_items": [],
+                    "path": format!("chapter_{}.md", i + 1),
+                    "source_path": format!("chapter_{}.md", i + 1),
+                    "parent_names": []
                 }
-            ],
+            }))
+            .collect();
+
+        serde_json::from_value(json!({
+            "sections": sections,
             "__non_exhaustive": null
         }))
         .unwrap()
@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+/// The shape an infobox is expected to have, checked with [`Infobox::validate`].
+///
+/// [`Infobox::validate`]: crate::Infobox::validate
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct InfoboxSchema {
+    /// Fields the infobox must have.
+    pub required_fields: Vec<String>,
+    /// Fields the infobox may have besides the required ones.
+    pub optional_fields: Vec<String>,
+    /// Whether the infobox may have images.
+    pub allow_images: bool,
+}
+
+impl Default for InfoboxSchema {
+    fn default() -> Self {
+        Self {
+            required_fields: Vec::new(),
+            optional_fields: Vec::new(),
+            allow_images: true,
+        }
+    }
+}
+
+impl InfoboxSchema {
+    /// Whether `name` is one of the schema's required or optional fields.
+    pub fn allows_field(&self, name: &str) -> bool {
+        self.required_fields.iter()
+            .chain(&self.optional_fields)
+            .any(|field| field == name)
+    }
+}